@@ -0,0 +1,63 @@
+;tape 65536
+A brainfuck interpreter written in brainfuck
+
+It reads a program up to an exclamation mark or the end of the input and then
+runs that program on the rest of the input
+
+Every instruction and every cell of the program's tape takes up sixteen cells
+The instructions are stored right of the starting cell and the program's tape
+grows left of it into the far end of the tape so this only runs with a wrapping
+pointer
+A tape of 65536 cells holds programs of up to about four thousand instructions
+Cells are as wide as the outer interpreter's and the end of the input behaves
+the same way for the interpreted program
+
+<<<<<<<<<<<<<<<<+>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>+[-<[-],[->>+>+<<<]>>>[-<<<+
+>>>]<------------------------------------------->>+<<[>>-<<[-]]<<[->>+>+<<<]>>>[
+-<<<+>>>]<--------------------------------------------->>>+<<<[>>>-<<<[-]]<<[->>
++>+<<<]>>>[-<<<+>>>]<-----------------------------------------------------------
+->>>>+<<<<[>>>>-<<<<[-]]<<[->>+>+<<<]>>>[-<<<+>>>]<-----------------------------
+--------------------------------->>>>>+<<<<<[>>>>>-<<<<<[-]]<<[->>+>+<<<]>>>[-<<
+<+>>>]<---------------------------------------------->>>>>>+<<<<<<[>>>>>>-<<<<<<
+[-]]<<[->>+>+<<<]>>>[-<<<+>>>]<-------------------------------------------->>>>>
+>>+<<<<<<<[>>>>>>>-<<<<<<<[-]]<<[->>+>+<<<]>>>[-<<<+>>>]<-----------------------
+-------------------------------------------------------------------->>>>>>>>+<<<
+<<<<<[>>>>>>>>-<<<<<<<<[-]]<<[->>+>+<<<]>>>[-<<<+>>>]<--------------------------
+------------------------------------------------------------------->>>>>>>>>+<<<
+<<<<<<[>>>>>>>>>-<<<<<<<<<[-]]<<[->>+>+<<<]>>>[-<<<+>>>]<-----------------------
+---------->>>>>>>>>>+<<<<<<<<<<[>>>>>>>>>>-<<<<<<<<<<[-]]<<[->>+>+<<<]>>>[-<<<+>
+>>]>>>>>>>>>+<<<<<<<<<<[>>>>>>>>>>-<<<<<<<<<<[-]]<+>>>>>>>>>>>[-<<<<<<<<<<<->>>>
+>>>>>>>]<<<<<<<<[-<<<<<<+>>>>>+>]<[->+<]>>[-<<<<<<<+>>>>>+>>]<<[->>+<<]>>>[-<<<<
+<<<<+>>>>>+>>>]<<<[->>>+<<<]>>>>[-<<<<<<<<<+>>>>>+>>>>]<<<<[->>>>+<<<<]>>>>>[-<<
+<<<<<<<<+>>>>>+>>>>>]<<<<<[->>>>>+<<<<<]>>>>>>[-<<<<<<<<<<<+>>>>>+>>>>>>]<<<<<<[
+->>>>>>+<<<<<<]>>>>>>>[-<<<<<<<<<<<<+>>>>>+>>>>>>>]<<<<<<<[->>>>>>>+<<<<<<<]>>>>
+>>>>[-<<<<<<<<<<<<<+>>>>>+>>>>>>>>]<<<<<<<<[->>>>>>>>+<<<<<<<<]<<<[-]<<[>>>[->>>
+>>>>>>>>>>>>>+<<<<<<<<<<<<<<<<]>>>>>>>>>>>>>]>>>]<<<<<<<<<<<<<<<<<<<[<<<<<<<<<<<
+<<<<<]>>>>>>>>>>>>>>>>>[>>>>>>>>>>>>>>>>]<[>>>>>>[-<<+>+>]<[->+<]<[-<<<<[<<<<<<<
+<<<<<<<<<]<<<<<<<<<<<<<<<[<<<<<<<<<<<<<<<<]>+<<[>>>>>>>>>>>>>>>>]>>>>>>>>>>>>>>>
+>>[>>>>>>>>>>>>>>>>]>>>]>>>[-<<<+>+>>]<<[->>+<<]<[-<<<<[<<<<<<<<<<<<<<<<]<<<<<<<
+<<<<<<<<[<<<<<<<<<<<<<<<<]>-<<[>>>>>>>>>>>>>>>>]>>>>>>>>>>>>>>>>>[>>>>>>>>>>>>>>
+>>]>>>]>>>>[-<<<<+>+>>>]<<<[->>>+<<<]<[-<<<<[<<<<<<<<<<<<<<<<]<<<<<<<<<<<<<<<[<<
+<<<<<<<<<<<<<<]>>>>>>>>>>>>>>>>-<[>>>>>>>>>>>>>>>>]>>>>>>>>>>>>>>>>>[>>>>>>>>>>>
+>>>>>]>>>]>>>>>[-<<<<<+>+>>>>]<<<<[->>>>+<<<<]<[-<<<<[<<<<<<<<<<<<<<<<]<<<<<<<<<
+<<<<<<[<<<<<<<<<<<<<<<<]+<<<<<<<<<<<<<<<<<[-]+[>>>>>>>>>>>>>>>>]>>>>>>>>>>>>>>>>
+>[>>>>>>>>>>>>>>>>]>>>]>>>>>>[-<<<<<<+>+>>>>>]<<<<<[->>>>>+<<<<<]<[-<<<<[<<<<<<<
+<<<<<<<<<]<<<<<<<<<<<<<<<[<<<<<<<<<<<<<<<<]>.<<[>>>>>>>>>>>>>>>>]>>>>>>>>>>>>>>>
+>>[>>>>>>>>>>>>>>>>]>>>]>>>>>>>[-<<<<<<<+>+>>>>>>]<<<<<<[->>>>>>+<<<<<<]<[-<<<<[
+<<<<<<<<<<<<<<<<]<<<<<<<<<<<<<<<[<<<<<<<<<<<<<<<<]>,<<[>>>>>>>>>>>>>>>>]>>>>>>>>
+>>>>>>>>>[>>>>>>>>>>>>>>>>]>>>]>>>>>>>>[-<<<<<<<<+>+>>>>>>>]<<<<<<<[->>>>>>>+<<<
+<<<<]<[-<<<<[<<<<<<<<<<<<<<<<]<<<<<<<<<<<<<<<[<<<<<<<<<<<<<<<<]>[->+>>+<<<]>>>[-
+<<<+>>>]<+<[>-<[-]]>[-<<<<[>>>>>>>>>>>>>>>>]>>>>>>>>>>>>>>>>>[>>>>>>>>>>>>>>>>]>
+>>>>>>>>>>>>+<<<<<<<<<<<<<<[<<<<<<<<<<<<<<<<]<<<<<<<<<<<<<<<[<<<<<<<<<<<<<<<<]>>
+>]<<<<[>>>>>>>>>>>>>>>>]>>>>>>>>>>>>>>>>>[>>>>>>>>>>>>>>>>]>>>]>>>>>>>>>[-<<<<<<
+<<<+>+>>>>>>>>]<<<<<<<<[->>>>>>>>+<<<<<<<<]<[-<<<<[<<<<<<<<<<<<<<<<]<<<<<<<<<<<<
+<<<[<<<<<<<<<<<<<<<<]>[->+>>+<<<]>>>[-<<<+>>>]<<[[-]>+<]>[-<<<<[>>>>>>>>>>>>>>>>
+]>>>>>>>>>>>>>>>>>[>>>>>>>>>>>>>>>>]>>>>>>>>>>>>>>+<<<<<<<<<<<<<<<[<<<<<<<<<<<<<
+<<<]<<<<<<<<<<<<<<<[<<<<<<<<<<<<<<<<]>>>]<<<<[>>>>>>>>>>>>>>>>]>>>>>>>>>>>>>>>>>
+[>>>>>>>>>>>>>>>>]>>>]>>>>>>>>>>[-<<<<<<<<<<<+[[->>>>>>>>>>>>>>>>+<<<<<<<<<<<<<<
+<<]<<+>>>>>>>>>>>>>>>>>>>>>>>>>>>[-<<<<<<<<+>+>>>>>>>]<<<<<<<[->>>>>>>+<<<<<<<]<
+[-<+>]>>>>>>>>>[-<<<<<<<<<+>+>>>>>>>>]<<<<<<<<[->>>>>>>>+<<<<<<<<]<[-<->]<]>>>>>
+>>>>>>]>[-<<<<<<<<<<<<+[[-<<<<<<<<<<<<<<<<+>>>>>>>>>>>>>>>>]<<<<<<<<<<<<<<<<<<->
+>>>>>>>>>>>[-<<<<<<<<<+>+>>>>>>>>]<<<<<<<<[->>>>>>>>+<<<<<<<<]<[-<+>]>>>>>>>>[-<
+<<<<<<<+>+>>>>>>>]<<<<<<<[->>>>>>>+<<<<<<<]<[-<->]<]>>>>>>>>>>>>]<<<<<<<<<<<<<<+
+>>>>>>>>>>>>>>>]
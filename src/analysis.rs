@@ -0,0 +1,201 @@
+use std::collections::BTreeMap;
+
+use crate::{
+    cli::ListFormat,
+    program::{Instruction, Program},
+};
+
+/// The combined effect of a straight-line run of instructions, i.e. everything between two loop
+/// boundaries.
+#[derive(Debug, Default)]
+pub struct BlockEffect {
+    /// Number of enclosing loops.
+    pub depth: usize,
+    pub instructions: usize,
+    /// Net movement of the pointer.
+    pub pointer_delta: isize,
+    /// Net number of increments of each cell, by offset from the pointer at the block's start,
+    /// counted from the cell's last clear if it has one. Cells whose net change is zero are
+    /// omitted.
+    pub cell_deltas: BTreeMap<isize, i64>,
+    /// I/O and debug events in order, with the offset of the cell they use.
+    pub events: Vec<(Event, isize)>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Event {
+    Output,
+    Input,
+    Debug,
+    Clear,
+    /// The cell was added to others and cleared, see [`Instruction::MultiplyAdd`].
+    MultiplyAdd,
+}
+
+impl Event {
+    fn name(self) -> &'static str {
+        match self {
+            Event::Output => "out",
+            Event::Input => "in",
+            Event::Debug => "debug",
+            Event::Clear => "clear",
+            Event::MultiplyAdd => "mul",
+        }
+    }
+}
+
+/// Splits the program into straight-line blocks, in source order, and computes their effects.
+pub fn block_effects(program: &Program) -> Vec<BlockEffect> {
+    let mut blocks = Vec::new();
+    collect_blocks(&program.instructions, 0, &mut blocks);
+    blocks
+}
+
+fn collect_blocks(instructions: &[Instruction], depth: usize, blocks: &mut Vec<BlockEffect>) {
+    let mut block = BlockEffect {
+        depth,
+        ..Default::default()
+    };
+
+    for instruction in instructions {
+        let offset = block.pointer_delta;
+        match instruction {
+            Instruction::Increment => *block.cell_deltas.entry(offset).or_default() += 1,
+            Instruction::Decrement => *block.cell_deltas.entry(offset).or_default() -= 1,
+            Instruction::Add(n) => *block.cell_deltas.entry(offset).or_default() += *n as i64,
+            Instruction::Right => block.pointer_delta += 1,
+            Instruction::Left => block.pointer_delta -= 1,
+            Instruction::Move(offset) => block.pointer_delta += offset,
+            Instruction::Output => block.events.push((Event::Output, offset)),
+            Instruction::OutputBytes(bytes) => {
+                block
+                    .events
+                    .extend(bytes.iter().map(|_| (Event::Output, offset)));
+            }
+            Instruction::InitCells {
+                base_offset,
+                values,
+            } => {
+                for (i, &value) in values.iter().enumerate() {
+                    let cell = offset + (base_offset + i) as isize;
+                    *block.cell_deltas.entry(cell).or_default() += value as i64;
+                }
+            }
+            Instruction::Input => block.events.push((Event::Input, offset)),
+            Instruction::Debug => block.events.push((Event::Debug, offset)),
+            Instruction::SetZero => {
+                block.cell_deltas.remove(&offset);
+                block.events.push((Event::Clear, offset));
+            }
+            Instruction::Set(value) => {
+                block.events.push((Event::Clear, offset));
+                block.cell_deltas.insert(offset, *value as i64);
+            }
+            Instruction::MultiplyAdd { .. } => {
+                block.cell_deltas.remove(&offset);
+                block.events.push((Event::MultiplyAdd, offset));
+            }
+            Instruction::Scan { .. } => {
+                // the scan ends on a cell that isn't known ahead of time, so offsets after it
+                // are relative to that cell
+                finish_block(
+                    std::mem::replace(
+                        &mut block,
+                        BlockEffect {
+                            depth,
+                            ..Default::default()
+                        },
+                    ),
+                    blocks,
+                );
+                continue;
+            }
+            Instruction::Loop(body) => {
+                finish_block(
+                    std::mem::replace(
+                        &mut block,
+                        BlockEffect {
+                            depth,
+                            ..Default::default()
+                        },
+                    ),
+                    blocks,
+                );
+                collect_blocks(body, depth + 1, blocks);
+                continue;
+            }
+        }
+        block.instructions += 1;
+    }
+
+    finish_block(block, blocks);
+}
+
+fn finish_block(mut block: BlockEffect, blocks: &mut Vec<BlockEffect>) {
+    if block.instructions > 0 {
+        block.cell_deltas.retain(|_, delta| *delta != 0);
+        blocks.push(block);
+    }
+}
+
+pub fn print_effects(blocks: &[BlockEffect], format: ListFormat) {
+    match format {
+        ListFormat::Text => {
+            println!(
+                "{:>5} {:>5} {:>12} {:>7}  {:<30} io",
+                "block", "depth", "instructions", "pointer", "cells"
+            );
+            for (index, block) in blocks.iter().enumerate() {
+                let cells: Vec<_> = block
+                    .cell_deltas
+                    .iter()
+                    .map(|(offset, delta)| format!("{offset}:{delta:+}"))
+                    .collect();
+                let events: Vec<_> = block
+                    .events
+                    .iter()
+                    .map(|(event, offset)| format!("{}@{offset}", event.name()))
+                    .collect();
+                let row = format!(
+                    "{index:>5} {:>5} {:>12} {:>+7}  {:<30} {}",
+                    block.depth,
+                    block.instructions,
+                    block.pointer_delta,
+                    cells.join(" "),
+                    events.join(" ")
+                );
+                println!("{}", row.trim_end());
+            }
+        }
+        ListFormat::Json => {
+            let blocks: Vec<_> = blocks
+                .iter()
+                .enumerate()
+                .map(|(index, block)| {
+                    let cells: Vec<_> = block
+                        .cell_deltas
+                        .iter()
+                        .map(|(offset, delta)| format!("\"{offset}\":{delta}"))
+                        .collect();
+                    let events: Vec<_> = block
+                        .events
+                        .iter()
+                        .map(|(event, offset)| {
+                            format!("{{\"event\":\"{}\",\"offset\":{offset}}}", event.name())
+                        })
+                        .collect();
+                    format!(
+                        "{{\"block\":{index},\"depth\":{},\"instructions\":{},\
+                         \"pointer_delta\":{},\"cell_deltas\":{{{}}},\"io\":[{}]}}",
+                        block.depth,
+                        block.instructions,
+                        block.pointer_delta,
+                        cells.join(","),
+                        events.join(",")
+                    )
+                })
+                .collect();
+            println!("[{}]", blocks.join(","));
+        }
+    }
+}
@@ -1,34 +1,819 @@
-use std::path::PathBuf;
+use std::{
+    env::{self, VarError},
+    ffi::OsString,
+    iter,
+    path::PathBuf,
+};
 
-use clap::{value_parser, Parser, Subcommand, ValueEnum};
+use clap::{
+    Arg, ArgMatches, CommandFactory, Parser, Subcommand, ValueEnum, builder::PossibleValuesParser,
+    error::ErrorKind, parser::ValueSource, value_parser,
+};
+
+use crate::{compiler, config::Config, interpreter::DEFAULT_DEBUG_WINDOW, program::Program};
+
+/// Environment variable holding default options, e.g. `--array-size 100000 --eof-behaviour zero`.
+pub const OPTS_ENV_VAR: &str = "BRAINLIFT_OPTS";
+
+/// Tape size used if neither `--array-size` nor a `;tape` directive in the program sets one.
+pub const DEFAULT_ARRAY_SIZE: u32 = 30_000;
 
 #[derive(Parser, Debug)]
+#[command(args_override_self = true)]
 pub struct Args {
     #[command(subcommand)]
-    pub command: Commands,
+    pub command: Option<Commands>,
 
-    #[arg(long, default_value_t = 30_000, value_parser = value_parser!(u32).range(1..))]
-    pub array_size: u32,
+    /// Number of cells on the tape [default: 30000, or the program's `;tape` directive]
+    #[arg(long, value_parser = value_parser!(u32).range(1..))]
+    pub array_size: Option<u32>,
 
     #[arg(long, value_enum, default_value_t = EofBehaviour::Ignore)]
     pub eof_behaviour: EofBehaviour,
+
+    /// Width of a cell in bits. Cells wrap around at the chosen width, `.` writes the lowest
+    /// byte of the cell unless `--wide-output` says otherwise
+    #[arg(long, value_enum, value_name = "BITS", default_value_t = CellSize::Bits8)]
+    pub cell_size: CellSize,
+
+    /// What `.` writes for cells wider than 8 bits, whose values might not fit in a byte
+    #[arg(long, value_enum, value_name = "MODE", default_value_t = WideOutput::LowByte)]
+    pub wide_output: WideOutput,
+
+    /// Make the tape circular: moving past either end continues at the other one, instead of
+    /// being an error
+    #[arg(long)]
+    pub pointer_wrap: bool,
+
+    /// Let the pointer move left of the starting cell. The interpreter grows the tape at the
+    /// front, compiled programs start in the middle of the tape instead
+    #[arg(long, conflicts_with = "pointer_wrap")]
+    pub signed_tape: bool,
+
+    /// Allocate the interpreter's whole tape up front instead of growing it as the pointer
+    /// moves, like compiled programs do
+    #[arg(long, conflicts_with = "signed_tape")]
+    pub fixed_tape: bool,
+
+    /// Architecture to compile for, see the `targets` command. Objects for other architectures
+    /// than the host's can only be emitted with `--emit obj`
+    #[arg(
+        long,
+        global = true,
+        default_value = compiler::DEFAULT_TARGET,
+        value_parser = PossibleValuesParser::new(compiler::supported_targets())
+    )]
+    pub target: String,
+
+    /// Optimization level: 0 runs no passes, 1 enables `--fold-add`, `--fold-moves`,
+    /// `--fold-clears`, `--fold-multiply`, `--fold-set` and `--fold-scans`, 2 additionally
+    /// enables `--fold-init` and `--static-eval`. Passes that assume 8-bit cells are left out
+    /// for wider cells
+    #[arg(
+        short = 'O',
+        long = "optimize",
+        value_name = "LEVEL",
+        default_value_t = 0,
+        value_parser = value_parser!(u8).range(0..=2)
+    )]
+    pub optimize: u8,
+
+    /// Evaluate programs that read no input ahead of time, reducing them to their output
+    #[arg(long)]
+    pub static_eval: bool,
+
+    /// Fold the cell updates at the start of programs into a single copy of the initial tape
+    #[arg(long)]
+    pub fold_init: bool,
+
+    /// Combine runs of `+` and `-` into single additions
+    #[arg(long)]
+    pub fold_add: bool,
+
+    /// Combine runs of `>` and `<` into single moves
+    #[arg(long)]
+    pub fold_moves: bool,
+
+    /// Replace the clear loops `[-]` and `[+]` with a direct store of zero
+    #[arg(long)]
+    pub fold_clears: bool,
+
+    /// Replace loops that add the current cell to others, like `[->+<]` or `[->++>-<<]`, with a
+    /// single multiplication per target
+    #[arg(long)]
+    pub fold_multiply: bool,
+
+    /// Replace clearing a cell and then adding to it, like `[-]+++`, with a single store of the
+    /// resulting value
+    #[arg(long)]
+    pub fold_set: bool,
+
+    /// Replace loops that only move the pointer, like `[>]` and `[<]`, with a direct search for
+    /// the next zero cell
+    #[arg(long)]
+    pub fold_scans: bool,
+
+    /// Run exactly these passes, in this order, instead of those chosen by `-O` and the
+    /// `--fold-*` flags, e.g. `--opt-passes clears,add,moves`. A pass can be listed more than once
+    #[arg(
+        long,
+        value_enum,
+        value_name = "PASSES",
+        value_delimiter = ',',
+        conflicts_with_all = [
+            "optimize", "static_eval", "fold_init", "fold_add", "fold_moves", "fold_clears",
+            "fold_multiply", "fold_set", "fold_scans",
+        ]
+    )]
+    pub opt_passes: Option<Vec<Pass>>,
+
+    /// Reject programs with more than this many instructions
+    #[arg(long, value_name = "N")]
+    pub max_instructions: Option<usize>,
+
+    /// Treat everything after the first `!` in the source as the input for `,`, unless
+    /// `--input-file` or `--input-string` is given
+    #[arg(long)]
+    pub embedded_input: bool,
+
+    /// Stop interpreted programs with an error after executing this many instructions, counting
+    /// every loop iteration as one
+    #[arg(long, value_name = "N")]
+    pub max_steps: Option<u64>,
+
+    /// Print the instructions of the program after the optimization passes to stderr, one per
+    /// line, before running or compiling it
+    #[arg(long)]
+    pub dump_ir: bool,
+
+    /// Print the program after the optimization passes as the flat instructions executed by
+    /// `run --flat` to stdout and exit, one per line with its index and loop jumps resolved to
+    /// the index they continue at
+    #[arg(long)]
+    pub dump_bytecode: bool,
+
+    /// Print additional diagnostics to stderr
+    #[arg(long)]
+    pub verbose: bool,
+
+    /// Print how long `run` took to parse and to execute the program to stderr
+    #[arg(long)]
+    pub time: bool,
+
+    /// Print the effective configuration, after applying all defaults, and exit
+    #[arg(long)]
+    pub print_config: bool,
+}
+
+impl Args {
+    /// Returns the tape size for `program`: an explicit `--array-size` takes precedence over the
+    /// program's `;tape` directive, which takes precedence over [`DEFAULT_ARRAY_SIZE`].
+    ///
+    /// ```
+    /// use brainlift::{Program, cli::{Args, DEFAULT_ARRAY_SIZE}};
+    /// use clap::Parser;
+    ///
+    /// let program = Program::parse(";tape 5000\n+[-]").unwrap();
+    /// let args = Args::parse_from(["brainlift", "--array-size", "100"]);
+    /// assert_eq!(args.array_size(&program), 100);
+    ///
+    /// let args = Args::parse_from(["brainlift"]);
+    /// assert_eq!(args.array_size(&program), 5000);
+    /// assert_eq!(args.array_size(&Program::parse("+[-]").unwrap()), DEFAULT_ARRAY_SIZE as usize);
+    /// ```
+    pub fn array_size(&self, program: &Program) -> usize {
+        self.array_size
+            .or(program.tape_size)
+            .unwrap_or(DEFAULT_ARRAY_SIZE) as usize
+    }
+
+    /// Returns the optimization passes to run, in order: those listed by `--opt-passes`, or else
+    /// the ones turned on by the `--fold-*` flags and `--static-eval`, in the order of [`Pass`].
+    ///
+    /// ```
+    /// use brainlift::cli::{Args, Pass};
+    /// use clap::Parser;
+    ///
+    /// let args = Args::parse_from(["brainlift", "--fold-moves", "--fold-clears"]);
+    /// assert_eq!(args.passes(), [Pass::Moves, Pass::Clears]);
+    ///
+    /// let args = Args::parse_from(["brainlift", "--opt-passes", "clears,add,clears"]);
+    /// assert_eq!(args.passes(), [Pass::Clears, Pass::Add, Pass::Clears]);
+    ///
+    /// // unknown passes are rejected with the list of available ones
+    /// let error = Args::try_parse_from(["brainlift", "--opt-passes", "add,rle"]).unwrap_err();
+    /// assert!(error.to_string().contains("invalid value 'rle'"));
+    /// assert!(error.to_string().contains("[possible values: add, moves, clears, multiply, set"));
+    /// ```
+    pub fn passes(&self) -> Vec<Pass> {
+        if let Some(passes) = &self.opt_passes {
+            return passes.clone();
+        }
+        [
+            (self.fold_add, Pass::Add),
+            (self.fold_moves, Pass::Moves),
+            (self.fold_clears, Pass::Clears),
+            (self.fold_multiply, Pass::Multiply),
+            (self.fold_set, Pass::Set),
+            (self.fold_scans, Pass::Scans),
+            (self.fold_init, Pass::Init),
+            (self.static_eval, Pass::StaticEval),
+        ]
+        .into_iter()
+        .filter_map(|(enabled, pass)| enabled.then_some(pass))
+        .collect()
+    }
+
+    /// Parses the command line, taking the options it doesn't give from the whitespace-separated
+    /// options in [`OPTS_ENV_VAR`] and then from the config file, so the precedence is
+    /// command line > environment > config file > built-in defaults.
+    pub fn parse_with_defaults() -> Self {
+        let env_defaults = match env::var(OPTS_ENV_VAR) {
+            Ok(opts) => opts,
+            Err(VarError::NotPresent) => String::new(),
+            Err(VarError::NotUnicode(_)) => {
+                eprintln!("warning: ignoring {OPTS_ENV_VAR}: not valid UTF-8");
+                String::new()
+            }
+        };
+        let defaults = [
+            env_defaults
+                .split_whitespace()
+                .map(OsString::from)
+                .collect(),
+            Config::load().to_args(),
+        ];
+
+        let mut args = Self::parse_over_defaults(env::args_os(), &defaults);
+        if args.cell_size != CellSize::Bits8
+            && let Some(pass) = args
+                .passes()
+                .into_iter()
+                .find(|pass| pass.needs_byte_cells())
+        {
+            let name = pass.to_possible_value().expect("no pass is skipped");
+            Self::command()
+                .error(
+                    ErrorKind::ArgumentConflict,
+                    format!("the `{}` pass only supports 8-bit cells", name.get_name()),
+                )
+                .exit();
+        }
+        // the reports describe the object, which isn't produced for IR
+        if let Some(Commands::Compile {
+            emit: Emit::Clif,
+            size_report,
+            list_symbols,
+            emit_callgraph,
+            ..
+        }) = &args.command
+            && let Some(report) = [
+                (*size_report, "--size-report"),
+                (*list_symbols, "--list-symbols"),
+                (emit_callgraph.is_some(), "--emit-callgraph"),
+            ]
+            .into_iter()
+            .find_map(|(given, name)| given.then_some(name))
+        {
+            Self::command()
+                .error(
+                    ErrorKind::ArgumentConflict,
+                    format!("`{report}` can't be used with `--emit clif`"),
+                )
+                .exit();
+        }
+        args.apply_optimization_level();
+        args
+    }
+
+    /// Parses `command_line` like [`Parser::parse_from`], taking the global options it doesn't
+    /// give from `defaults`, a list of option sets in decreasing precedence. An option from a set
+    /// is left out if a source with higher precedence gives it or an option conflicting with it,
+    /// so that e.g. `--signed-tape` on the command line overrides `pointer_wrap` in the file.
+    ///
+    /// ```
+    /// use brainlift::{cli::Args, config::Config};
+    ///
+    /// let file = Config::parse("pointer_wrap = true\narray_size = 100").unwrap().to_args();
+    /// let args = Args::parse_over_defaults(["brainlift", "--signed-tape"], &[file.clone()]);
+    /// assert!(args.signed_tape && !args.pointer_wrap);
+    /// assert_eq!(args.array_size, Some(100));
+    ///
+    /// let env = vec!["--array-size=200".into(), "--signed-tape".into()];
+    /// let args = Args::parse_over_defaults(["brainlift"], &[env, file]);
+    /// assert!(args.signed_tape && !args.pointer_wrap);
+    /// assert_eq!(args.array_size, Some(200));
+    /// ```
+    pub fn parse_over_defaults<I, T>(command_line: I, defaults: &[Vec<OsString>]) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString>,
+    {
+        let command_line: Vec<OsString> = command_line.into_iter().map(Into::into).collect();
+        let program = command_line.first().cloned().unwrap_or_default();
+        let command = Self::command();
+        let given_in = |matches: &ArgMatches, arg: &Arg| {
+            matches.value_source(arg.get_id().as_str()) == Some(ValueSource::CommandLine)
+        };
+        let conflict = |a: &Arg, b: &Arg| {
+            a.get_id() == b.get_id()
+                || command.get_arg_conflicts_with(a).contains(&b)
+                || command.get_arg_conflicts_with(b).contains(&a)
+        };
+
+        let matches = command.clone().get_matches_from(&command_line);
+        let mut given: Vec<&Arg> = command
+            .get_arguments()
+            .filter(|arg| given_in(&matches, arg))
+            .collect();
+
+        let mut default_args = Vec::new();
+        for defaults in defaults {
+            let matches = command
+                .clone()
+                .get_matches_from(iter::once(&program).chain(defaults));
+            for arg in command
+                .get_arguments()
+                .filter(|arg| given_in(&matches, arg))
+            {
+                if given.iter().any(|other| conflict(arg, other)) {
+                    continue;
+                }
+                given.push(arg);
+
+                let long = arg.get_long().expect("global options have a long name");
+                if !arg.get_action().takes_values() {
+                    default_args.push(format!("--{long}").into());
+                    continue;
+                }
+                for value in matches.get_raw(arg.get_id().as_str()).into_iter().flatten() {
+                    let mut option = OsString::from(format!("--{long}="));
+                    option.push(value);
+                    default_args.push(option);
+                }
+            }
+        }
+
+        Self::parse_from(
+            iter::once(program)
+                .chain(default_args)
+                .chain(command_line.into_iter().skip(1)),
+        )
+    }
+
+    /// Turns on the passes included in the `-O` level, in addition to those enabled explicitly.
+    fn apply_optimization_level(&mut self) {
+        // these passes compute cell values, wrapping around at 8 bits
+        let byte_cells = self.cell_size == CellSize::Bits8;
+
+        if self.optimize >= 1 {
+            self.fold_add |= byte_cells;
+            self.fold_moves = true;
+            self.fold_clears = true;
+            self.fold_multiply = true;
+            self.fold_set |= byte_cells;
+            self.fold_scans = true;
+        }
+        if self.optimize >= 2 {
+            self.fold_init |= byte_cells;
+            self.static_eval |= byte_cells;
+        }
+    }
 }
 
 #[derive(Subcommand, Debug)]
 pub enum Commands {
     Run {
+        /// The program to run, or `-` to read it from stdin
         input: PathBuf,
+
+        /// Print a histogram of the values written to cells to stderr after the run
+        #[arg(long)]
+        value_histogram: bool,
+
+        /// Treat `,` as EOF instead of blocking when stdin is a terminal or has no pending data
+        #[arg(long)]
+        deterministic_input: bool,
+
+        /// Strip null bytes from the end of the output, or from both ends. This alters the
+        /// program's output
+        #[arg(
+            long,
+            value_enum,
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = "trailing"
+        )]
+        trim_output: Option<TrimOutput>,
+
+        /// Execute loops using a precomputed jump table instead of walking them recursively
+        #[arg(long)]
+        flat: bool,
+
+        /// Where to write the program's output. `null` discards it, e.g. for benchmarking
+        #[arg(long, value_enum, default_value_t = OutputTarget::Stdout)]
+        output: OutputTarget,
+
+        /// Write the output as-is, or as a hex dump like `xxd`
+        #[arg(long, value_enum, default_value_t = OutputFormat::Raw)]
+        output_format: OutputFormat,
+
+        /// Capacity of the output buffer. Larger buffers speed up programs with huge outputs, `0`
+        /// leaves only the line buffering of stdout. The buffer is always flushed before `,`
+        #[arg(long, value_name = "BYTES", default_value_t = 8 * 1024)]
+        output_buffer: usize,
+
+        /// Fail if a value above N is ever written to a cell
+        #[arg(long, value_name = "N")]
+        max_cell_value: Option<u32>,
+
+        /// Fail if `,` reads a byte that isn't ASCII
+        #[arg(long)]
+        strict_ascii_input: bool,
+
+        /// How much input `,` takes from stdin at once. With `line`, a whole line is read when
+        /// the previous one has been used up, and `,` then returns its bytes one by one
+        #[arg(long, value_enum, default_value_t = InputMode::Byte)]
+        input_mode: InputMode,
+
+        /// Read the input for `,` from this file instead of stdin
+        #[arg(long, value_name = "FILE", conflicts_with_all = ["input_string", "replay"])]
+        input_file: Option<PathBuf>,
+
+        /// Use this text as the input for `,` instead of stdin, EOF follows after it
+        #[arg(long, value_name = "TEXT", conflicts_with = "replay")]
+        input_string: Option<String>,
+
+        /// Record the settings and all input of this run to a file, so that it can be reproduced
+        /// with `--replay`
+        #[arg(long, value_name = "FILE", conflicts_with = "replay")]
+        record: Option<PathBuf>,
+
+        /// Run the program with the settings and input of a recording made with `--record`
+        #[arg(long, value_name = "FILE")]
+        replay: Option<PathBuf>,
+
+        /// Exit with the value of the cell under the pointer when the program ends, truncated to
+        /// a byte, so that programs can report failure
+        #[arg(long)]
+        exit_with_cell: bool,
+
+        /// Stop at every `#` and read debugger commands from the terminal, e.g. to step through
+        /// the program or look at the tape
+        #[arg(long)]
+        debug: bool,
+
+        /// Number of cells shown on either side of the pointer by `#`
+        #[arg(long, value_name = "N", default_value_t = DEFAULT_DEBUG_WINDOW)]
+        debug_window: usize,
     },
     Compile {
+        /// The program to compile, or `-` to read it from stdin
         input: PathBuf,
 
+        /// File to write to, defaults to the input's name without its extension for executables
+        /// and with `.o` for objects or `.clif` for IR, which can also be written to stdout with
+        /// `-`
         #[arg(short)]
         output: Option<PathBuf>,
+
+        /// What to produce. Executables are linked with `$CC`, or `cc` if it isn't set
+        #[arg(long, value_enum, default_value_t = Emit::Exe)]
+        emit: Emit,
+
+        /// Machine code optimization level of Cranelift, independent of the passes on the
+        /// program. Defaults to `none` for `-O0`, `speed` for `-O1` and `speed-and-size` for `-O2`
+        #[arg(long, value_enum)]
+        codegen_opt: Option<CodegenOpt>,
+
+        /// Name of the exported function, to call the program from other code. Executables need
+        /// the default, `main`
+        #[arg(long, value_name = "NAME", default_value = compiler::DEFAULT_ENTRY)]
+        entry: String,
+
+        /// Print the size of the generated code and of the object's sections to stderr
+        #[arg(long)]
+        size_report: bool,
+
+        /// Print the symbols the object imports and exports
+        #[arg(long)]
+        list_symbols: bool,
+
+        /// Make the compiled program exit with status 1 if writing its output fails
+        #[arg(long)]
+        check_output: bool,
+
+        /// Check every pointer move against the ends of the tape and abort the compiled program
+        /// if it leaves the tape, like the interpreter does
+        #[arg(long)]
+        check_bounds: bool,
+
+        /// Call the host's `int should_continue(void)` on every loop iteration and exit with
+        /// status 1 once it returns 0
+        #[arg(long)]
+        interruptible: bool,
+
+        /// Don't free the tape before exiting, leaving it to the OS
+        #[arg(long)]
+        no_free: bool,
+
+        /// Calling convention of the generated entry function, defaults to the target's
+        #[arg(long, value_enum)]
+        call_conv: Option<CallConvention>,
+
+        /// Compile every N top-level instructions as a separate function, chained by tail calls,
+        /// to speed up compilation of huge programs
+        #[arg(long, value_name = "N", value_parser = value_parser!(u32).range(1..))]
+        chunk_size: Option<u32>,
+
+        /// Directory for caching objects, reused when the program and settings are unchanged.
+        /// Not used with `--size-report` and `--emit-callgraph`
+        #[arg(long)]
+        cache_dir: Option<PathBuf>,
+
+        /// Write the call graph of the generated functions in DOT format to stdout, or to the
+        /// given file
+        #[arg(long, value_name = "FILE", num_args = 0..=1, require_equals = true)]
+        emit_callgraph: Option<Option<PathBuf>>,
+
+        /// Where to allocate the tape. `stack` keeps every call of the entry function
+        /// independent, but only supports tapes of up to 1 MiB
+        #[arg(long, value_enum, default_value_t = TapeStorage::Heap)]
+        tape_storage: TapeStorage,
+
+        /// Link a position-independent executable. By default, the linker's default is used,
+        /// which is PIE on most distributions
+        #[arg(long, conflicts_with = "no_pie")]
+        pie: bool,
+
+        /// Link an executable that is loaded at a fixed address
+        #[arg(long)]
+        no_pie: bool,
+
+        /// Byte order of cells wider than 8 bits in the compiled program's memory and initial
+        /// tape data
+        #[arg(long, value_enum, value_name = "ORDER", default_value_t = CellEndian::Little)]
+        cell_endian: CellEndian,
+    },
+    /// Run two programs on the same input and compare their behaviour
+    Diff {
+        first: PathBuf,
+        second: PathBuf,
+
+        /// Input for `,`, both programs see EOF after it
+        #[arg(long)]
+        input: Option<String>,
+
+        /// Also compare the final tapes
+        #[arg(long)]
+        compare_tape: bool,
+    },
+    /// Run a program to its end or to its first `#`, then browse the tape in the terminal
+    Inspect { input: PathBuf },
+    /// Print analyses of a program's structure
+    Analyze {
+        input: PathBuf,
+
+        /// Print the net effect of each straight-line block between loop boundaries: pointer
+        /// movement, cell changes and I/O
+        #[arg(long, required = true)]
+        effects: bool,
+
+        #[arg(long, value_enum, default_value_t = ListFormat::Text)]
+        format: ListFormat,
+    },
+    /// Print statistics about a program, or about all programs in a directory
+    Stats {
+        path: PathBuf,
+
+        /// Also collect programs in subdirectories
+        #[arg(long)]
+        recursive: bool,
+
+        #[arg(long, value_enum, default_value_t = StatsFormat::Table)]
+        format: StatsFormat,
+    },
+    /// List the targets supported by this build
+    Targets {
+        #[arg(long, value_enum, default_value_t = ListFormat::Text)]
+        format: ListFormat,
+    },
+    /// Run a program and check its output against a golden file
+    Test {
+        program: PathBuf,
+
+        /// File holding the exact output the program is expected to produce
+        #[arg(long, value_name = "FILE")]
+        expect_file: PathBuf,
+
+        /// Input for `,`, the program sees EOF after it
+        #[arg(long)]
+        input: Option<String>,
+    },
+    /// Check that a program parses, without running it
+    Check {
+        input: PathBuf,
+
+        /// Also run the program without input and check that it prints its own source, ignoring
+        /// comments
+        #[arg(long)]
+        quine: bool,
+
+        /// Print nothing, only exit with status 1 if the program doesn't parse, e.g. for
+        /// `brainlift check -q program.b && echo ok`
+        #[arg(short, long, conflicts_with = "quine")]
+        quiet: bool,
+    },
+    /// Run a program and report how often each instruction was executed and which loop iterated
+    /// the most. The report is written to stderr
+    Profile { input: PathBuf },
+    /// Write a program without its comments and whitespace. A `;tape` directive is kept
+    Minify {
+        /// The program to minify, or `-` to read it from stdin
+        input: PathBuf,
+
+        /// File to write the program to, defaults to stdout
+        #[arg(short)]
+        output: Option<PathBuf>,
+    },
+    /// Translate a program into C source
+    Transpile {
+        input: PathBuf,
+
+        /// File to write the C source to, defaults to stdout
+        #[arg(short)]
+        output: Option<PathBuf>,
+
+        /// Precede the code of each instruction with a comment giving its position and source,
+        /// and the name of instructions introduced by optimization passes, like `[-] set zero`
+        #[arg(long)]
+        annotate: bool,
     },
 }
 
-#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum EofBehaviour {
+    /// Leaves the cell unchanged.
     Ignore,
+    /// Sets the cell to 0.
     Zero,
+    /// Sets the cell to -1, i.e. 255 for 8-bit cells.
+    Negative,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CellSize {
+    #[value(name = "8")]
+    Bits8,
+    #[value(name = "16")]
+    Bits16,
+    #[value(name = "32")]
+    Bits32,
+}
+
+impl CellSize {
+    pub fn bytes(self) -> usize {
+        match self {
+            CellSize::Bits8 => 1,
+            CellSize::Bits16 => 2,
+            CellSize::Bits32 => 4,
+        }
+    }
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WideOutput {
+    /// Write the lowest byte of the value
+    LowByte,
+    /// Fail on values above 255, which likely come from a bug
+    Error,
+    /// Write the value as a Unicode character, encoded as UTF-8, and fail if it isn't one
+    Unicode,
+}
+
+/// An optimization pass for `--opt-passes`, named after the flag that turns it on.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Pass {
+    /// Combine runs of `+` and `-`, like `--fold-add`
+    Add,
+    /// Combine runs of `>` and `<`, like `--fold-moves`
+    Moves,
+    /// Replace clear loops with a store of zero, like `--fold-clears`
+    Clears,
+    /// Replace copy and multiply loops, like `--fold-multiply`
+    Multiply,
+    /// Combine clearing a cell and adding to it, like `--fold-set`
+    Set,
+    /// Replace scan loops with a search for a zero cell, like `--fold-scans`
+    Scans,
+    /// Fold the initial tape setup, like `--fold-init`
+    Init,
+    /// Evaluate programs that read no input ahead of time, like `--static-eval`
+    StaticEval,
+}
+
+impl Pass {
+    /// Whether the pass computes cell values, wrapping around at 8 bits.
+    pub fn needs_byte_cells(self) -> bool {
+        matches!(self, Pass::Add | Pass::Set | Pass::Init | Pass::StaticEval)
+    }
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ListFormat {
+    /// One entry per line
+    Text,
+    /// A JSON array of strings
+    Json,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatsFormat {
+    Table,
+    Csv,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputTarget {
+    Stdout,
+    Null,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Raw,
+    Hex,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Emit {
+    /// An object file, to be linked with libc
+    Obj,
+    /// An executable, linked with libc by the system's C compiler
+    Exe,
+    /// Cranelift's textual IR of the generated functions, before it is optimized
+    Clif,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CodegenOpt {
+    /// Translate the program as directly as possible, compiling fastest
+    None,
+    /// Optimize the generated code for speed
+    Speed,
+    /// Optimize the generated code for speed and size
+    SpeedAndSize,
+}
+
+impl CodegenOpt {
+    /// The level used for the `-O` level `optimize`, when none is given explicitly.
+    pub fn for_level(optimize: u8) -> Self {
+        match optimize {
+            0 => CodegenOpt::None,
+            1 => CodegenOpt::Speed,
+            _ => CodegenOpt::SpeedAndSize,
+        }
+    }
+
+    /// The value of Cranelift's `opt_level` setting.
+    pub fn setting(self) -> &'static str {
+        match self {
+            CodegenOpt::None => "none",
+            CodegenOpt::Speed => "speed",
+            CodegenOpt::SpeedAndSize => "speed_and_size",
+        }
+    }
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TapeStorage {
+    /// Allocate the tape with `calloc`
+    Heap,
+    /// Allocate the tape in the entry function's stack frame
+    Stack,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CellEndian {
+    Little,
+    Big,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CallConvention {
+    SystemV,
+    WindowsFastcall,
+    Tail,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputMode {
+    Byte,
+    Line,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrimOutput {
+    Trailing,
+    Both,
 }
@@ -1,45 +1,655 @@
-use std::{fs::File, io::Write, path::PathBuf};
+use std::{
+    collections::hash_map::RandomState,
+    env,
+    ffi::OsString,
+    fs::{self, File, OpenOptions},
+    hash::{BuildHasher, Hasher},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process::{self, Command},
+};
 
 use cranelift::{
-    codegen::ir::{BlockArg, FuncRef},
+    codegen::{
+        ir::{BlockArg, Endianness, ExternalName, FuncRef, InstructionData},
+        isa::CallConv,
+        print_errors::pretty_verifier_error,
+    },
     prelude::*,
 };
-use cranelift_module::{FuncId, FuncOrDataId, Linkage, Module};
+use cranelift_module::{DataDescription, FuncId, FuncOrDataId, Linkage, Module};
 use cranelift_object::{ObjectBuilder, ObjectModule};
+use object::{Object, ObjectSection, ObjectSymbol, SectionKind};
 
 use crate::{
-    cli::EofBehaviour,
+    cli::{
+        CallConvention, CellEndian, CellSize, CodegenOpt, Emit, EofBehaviour, TapeStorage,
+        WideOutput,
+    },
     program::{Instruction, Program},
 };
 
-const TARGET_TRIPLE: &str = "x86_64";
-const ENTRYPOINT_FUNCTION_SYMBOL: &str = "main";
+/// Architecture compiled for unless another one is chosen with [`Compiler::with_target`], the
+/// one brainlift itself runs on.
+pub const DEFAULT_TARGET: &str = env::consts::ARCH;
+/// Name of the generated function unless another one is chosen with [`Compiler::with_entry`].
+pub const DEFAULT_ENTRY: &str = "main";
 const GETCHAR_FUNCTION_SYMBOL: &str = "getchar";
 const PUTCHAR_FUNCTION_SYMBOL: &str = "putchar";
 const CALLOC_FUNCTION_SYMBOL: &str = "calloc";
 const FREE_FUNCTION_SYMBOL: &str = "free";
+const FFLUSH_FUNCTION_SYMBOL: &str = "fflush";
+const SHOULD_CONTINUE_FUNCTION_SYMBOL: &str = "should_continue";
+/// Functions the object may import, which the entry can't be named after.
+const IMPORTED_FUNCTION_SYMBOLS: [&str; 6] = [
+    GETCHAR_FUNCTION_SYMBOL,
+    PUTCHAR_FUNCTION_SYMBOL,
+    CALLOC_FUNCTION_SYMBOL,
+    FREE_FUNCTION_SYMBOL,
+    FFLUSH_FUNCTION_SYMBOL,
+    SHOULD_CONTINUE_FUNCTION_SYMBOL,
+];
+
+/// C compiler used to link executables if `$CC` isn't set.
+const DEFAULT_LINKER: &str = "cc";
+
+/// Largest tape in bytes that may be allocated on the stack, well below common thread stack
+/// sizes.
+pub const MAX_STACK_TAPE_SIZE: usize = 1 << 20;
+
+/// Architectures Cranelift has backends for, whether or not they are enabled in this build.
+const KNOWN_TARGETS: [&str; 6] = [
+    "x86_64", "aarch64", "riscv64", "s390x", "pulley32", "pulley64",
+];
+
+/// Returns the targets whose Cranelift backend is available in this build.
+pub fn supported_targets() -> Vec<&'static str> {
+    KNOWN_TARGETS
+        .into_iter()
+        .filter(|target| isa::lookup_by_name(target).is_ok())
+        .collect()
+}
 
 pub struct Compiler {
+    target: String,
+    entry: String,
+    codegen_opt: CodegenOpt,
     max_array_size: usize,
     eof_behaviour: EofBehaviour,
+    size_report: bool,
+    list_symbols: bool,
+    check_output: bool,
+    check_bounds: bool,
+    interruptible: bool,
+    free_tape: bool,
+    tape_storage: TapeStorage,
+    cell_size: CellSize,
+    cell_endian: CellEndian,
+    wide_output: WideOutput,
+    pointer_wrap: bool,
+    signed_tape: bool,
+    emit: Emit,
+    pie: Option<bool>,
+    call_conv: Option<CallConvention>,
+    chunk_size: Option<usize>,
+    cache_dir: Option<PathBuf>,
+    verbose: bool,
+    /// Where to write the call graph, `Some(None)` meaning stdout.
+    callgraph: Option<Option<PathBuf>>,
+    /// IR of the functions generated so far, for [`Emit::Clif`].
+    clif: Option<String>,
+    function_sizes: Vec<(String, u32)>,
+    /// Caller and callee of every call emitted.
+    calls: Vec<(String, String)>,
 }
 
 impl Compiler {
     pub fn new(max_array_size: usize, eof_behaviour: EofBehaviour) -> Self {
         Self {
+            target: DEFAULT_TARGET.to_owned(),
+            entry: DEFAULT_ENTRY.to_owned(),
+            codegen_opt: CodegenOpt::None,
             max_array_size,
             eof_behaviour,
+            size_report: false,
+            list_symbols: false,
+            check_output: false,
+            check_bounds: false,
+            interruptible: false,
+            free_tape: true,
+            tape_storage: TapeStorage::Heap,
+            cell_size: CellSize::Bits8,
+            cell_endian: CellEndian::Little,
+            wide_output: WideOutput::LowByte,
+            pointer_wrap: false,
+            signed_tape: false,
+            emit: Emit::Obj,
+            pie: None,
+            call_conv: None,
+            chunk_size: None,
+            cache_dir: None,
+            verbose: false,
+            callgraph: None,
+            clif: None,
+            function_sizes: Vec::new(),
+            calls: Vec::new(),
         }
     }
 
-    pub fn compile(mut self, program: &Program, output_file: PathBuf) {
+    /// Compile for the given architecture, one of [`supported_targets`], instead of
+    /// [`DEFAULT_TARGET`]. Objects for other architectures can't be linked by [`link`].
+    ///
+    /// ```
+    /// use brainlift::{cli::Emit, Compiler, EofBehaviour, Parser};
+    ///
+    /// let program = Parser::new("+.").parse().unwrap();
+    /// let object = std::env::temp_dir().join("brainlift-aarch64-doctest.o");
+    /// Compiler::new(30_000, EofBehaviour::Ignore)
+    ///     .with_target("aarch64".to_owned())
+    ///     .with_emit(Emit::Obj)
+    ///     .compile(&program, object.clone())
+    ///     .unwrap();
+    ///
+    /// let bytes = std::fs::read(&object).unwrap();
+    /// std::fs::remove_file(&object).unwrap();
+    /// assert_eq!(&bytes[..4], b"\x7fELF");
+    /// // `e_machine` of a little-endian ELF file, 183 being AArch64
+    /// assert_eq!(u16::from_le_bytes([bytes[18], bytes[19]]), 183);
+    /// ```
+    pub fn with_target(mut self, target: String) -> Self {
+        self.target = target;
+        self
+    }
+
+    /// Check the results of `putchar` and a final `fflush`, and exit with status 1 if writing
+    /// the output failed, e.g. because of a closed pipe or a full disk.
+    ///
+    /// ```
+    /// use brainlift::{Compiler, EofBehaviour, Parser, cli::Emit};
+    /// use std::os::unix::process::CommandExt;
+    ///
+    /// let program = Parser::new("+.").parse().unwrap();
+    /// let executable = std::env::temp_dir().join(format!("check-output-{}", std::process::id()));
+    /// let run = |check_output| {
+    ///     Compiler::new(10, EofBehaviour::Ignore)
+    ///         .with_emit(Emit::Exe)
+    ///         .with_check_output(check_output)
+    ///         .compile(&program, executable.clone())
+    ///         .unwrap();
+    ///
+    ///     // a pipe without a reader, with SIGPIPE ignored so that writing fails instead of
+    ///     // killing the program
+    ///     let (reader, writer) = std::io::pipe().unwrap();
+    ///     drop(reader);
+    ///     let mut command = std::process::Command::new(&executable);
+    ///     command.stdout(writer);
+    ///     unsafe {
+    ///         command.pre_exec(|| {
+    ///             libc::signal(libc::SIGPIPE, libc::SIG_IGN);
+    ///             Ok(())
+    ///         });
+    ///     }
+    ///     command.status().unwrap()
+    /// };
+    ///
+    /// assert_eq!(run(true).code(), Some(1));
+    /// assert_eq!(run(false).code(), Some(0));
+    /// std::fs::remove_file(&executable).unwrap();
+    /// ```
+    pub fn with_check_output(mut self, check_output: bool) -> Self {
+        self.check_output = check_output;
+        self
+    }
+
+    /// Compare the pointer against both ends of the tape after every move, and trap if it left
+    /// the tape instead of reading or writing memory outside of it. The interpreter reports the
+    /// same moves as errors. Has no effect on a wrapping pointer, which never leaves the tape.
+    ///
+    /// ```
+    /// use brainlift::{Compiler, EofBehaviour, Parser, cli::Emit};
+    ///
+    /// let program = Parser::new("<+").parse().unwrap();
+    /// let object = std::env::temp_dir().join(format!("check-bounds-{}.clif", std::process::id()));
+    /// Compiler::new(10, EofBehaviour::Ignore)
+    ///     .with_check_bounds(true)
+    ///     .with_emit(Emit::Clif)
+    ///     .compile(&program, object.clone())
+    ///     .unwrap();
+    ///
+    /// let clif = std::fs::read_to_string(&object).unwrap();
+    /// std::fs::remove_file(&object).unwrap();
+    /// assert!(clif.contains("trapnz"));
+    /// ```
+    pub fn with_check_bounds(mut self, check_bounds: bool) -> Self {
+        self.check_bounds = check_bounds;
+        self
+    }
+
+    /// Call the host-provided `int should_continue(void)` on every loop iteration, and exit with
+    /// status 1 as soon as it returns 0. Lets the host cancel a long-running program.
+    pub fn with_interruptible(mut self, interruptible: bool) -> Self {
+        self.interruptible = interruptible;
+        self
+    }
+
+    /// Whether to `free` the tape before returning. Leaving it to the OS when exiting saves a
+    /// little time and an imported symbol.
+    pub fn with_free_tape(mut self, free_tape: bool) -> Self {
+        self.free_tape = free_tape;
+        self
+    }
+
+    /// Where to allocate the tape. A stack tape needs no allocator and is never shared between
+    /// calls, but is limited to [`MAX_STACK_TAPE_SIZE`] bytes.
+    ///
+    /// ```
+    /// use brainlift::{Compiler, EofBehaviour, Parser, cli::{Emit, TapeStorage}};
+    ///
+    /// // writes "A\n", relying on a zeroed tape of its own
+    /// let program = Parser::new("++++++++[>++++++++<-]>+.[-]++++++++++.").parse().unwrap();
+    /// let dir = std::env::temp_dir().join(format!("tape-storage-{}", std::process::id()));
+    /// std::fs::create_dir_all(&dir).unwrap();
+    /// Compiler::new(10, EofBehaviour::Ignore)
+    ///     .with_emit(Emit::Obj)
+    ///     .with_entry("bf_run".to_owned())
+    ///     .with_tape_storage(TapeStorage::Stack)
+    ///     .compile(&program, dir.join("program.o"))
+    ///     .unwrap();
+    ///
+    /// // a host calling the program from 8 threads at once, 100 times each
+    /// let host = "#include <pthread.h>
+    /// int bf_run(void);
+    /// static void *run(void *arg) {
+    ///     for (int i = 0; i < 100; i++) bf_run();
+    ///     return arg;
+    /// }
+    /// int main(void) {
+    ///     pthread_t threads[8];
+    ///     for (int i = 0; i < 8; i++) pthread_create(&threads[i], 0, run, 0);
+    ///     for (int i = 0; i < 8; i++) pthread_join(threads[i], 0);
+    ///     return 0;
+    /// }";
+    /// std::fs::write(dir.join("host.c"), host).unwrap();
+    /// let linked = std::process::Command::new("cc")
+    ///     .current_dir(&dir)
+    ///     .args(["host.c", "program.o", "-pthread", "-o", "host"])
+    ///     .status()
+    ///     .unwrap();
+    /// assert!(linked.success());
+    ///
+    /// let output = std::process::Command::new(dir.join("host")).output().unwrap();
+    /// std::fs::remove_dir_all(&dir).unwrap();
+    /// assert!(output.status.success());
+    /// // the threads' characters may interleave, but none may see another tape
+    /// assert_eq!(output.stdout.iter().filter(|&&c| c == b'A').count(), 800);
+    /// assert_eq!(output.stdout.iter().filter(|&&c| c == b'\n').count(), 800);
+    /// assert_eq!(output.stdout.len(), 1600);
+    /// ```
+    pub fn with_tape_storage(mut self, tape_storage: TapeStorage) -> Self {
+        self.tape_storage = tape_storage;
+        self
+    }
+
+    /// Use cells of the given width, which wrap around at that width.
+    pub fn with_cell_size(mut self, cell_size: CellSize) -> Self {
+        self.cell_size = cell_size;
+        self
+    }
+
+    /// Store cells wider than a byte in the given byte order, little-endian by default, e.g. to
+    /// match the layout an external binary format expects. This also orders the bytes of the
+    /// initial tape data written by [`Instruction::InitCells`]. Cells are byte-swapped on every
+    /// load and store if the target uses the other order.
+    ///
+    /// ```
+    /// use brainlift::{Compiler, EofBehaviour, Program, cli::{CellEndian, CellSize}, optimizer};
+    /// use object::{Object, ObjectSection};
+    ///
+    /// let mut program = Program::parse("+++++>+++++++").unwrap();
+    /// optimizer::fold_initialization(&mut program, 10);
+    /// let object = std::env::temp_dir().join(format!("cell-endian-{}.o", std::process::id()));
+    /// let rodata = |cell_endian| {
+    ///     Compiler::new(10, EofBehaviour::Ignore)
+    ///         .with_cell_size(CellSize::Bits16)
+    ///         .with_cell_endian(cell_endian)
+    ///         .compile(&program, object.clone())
+    ///         .unwrap();
+    ///     let bytes = std::fs::read(&object).unwrap();
+    ///     let file = object::File::parse(&*bytes).unwrap();
+    ///     file.section_by_name(".rodata").unwrap().data().unwrap().to_vec()
+    /// };
+    ///
+    /// assert_eq!(rodata(CellEndian::Little), [5, 0, 7, 0]);
+    /// assert_eq!(rodata(CellEndian::Big), [0, 5, 0, 7]);
+    /// std::fs::remove_file(&object).unwrap();
+    /// ```
+    pub fn with_cell_endian(mut self, cell_endian: CellEndian) -> Self {
+        self.cell_endian = cell_endian;
+        self
+    }
+
+    /// Choose what `.` writes for cells wider than 8 bits, like
+    /// [`crate::Interpreter::with_wide_output`]. Values that can't be written in the chosen mode
+    /// make the program trap.
+    ///
+    /// ```
+    /// use brainlift::{Compiler, EofBehaviour, Parser, cli::{CellSize, Emit, WideOutput}};
+    ///
+    /// // writes 321, U+0141 as a character
+    /// let program = Parser::new("++++++++++++++++[>++++++++++++++++++++<-]>+.").parse().unwrap();
+    /// let executable = std::env::temp_dir().join(format!("wide-output-{}", std::process::id()));
+    /// let run = |wide_output| {
+    ///     Compiler::new(10, EofBehaviour::Ignore)
+    ///         .with_emit(Emit::Exe)
+    ///         .with_cell_size(CellSize::Bits16)
+    ///         .with_wide_output(wide_output)
+    ///         .compile(&program, executable.clone())
+    ///         .unwrap();
+    ///     std::process::Command::new(&executable).output().unwrap()
+    /// };
+    ///
+    /// assert_eq!(run(WideOutput::LowByte).stdout, b"A");
+    /// assert_eq!(run(WideOutput::Unicode).stdout, "\u{141}".as_bytes());
+    /// let trapped = run(WideOutput::Error);
+    /// assert!(!trapped.status.success() && trapped.stdout.is_empty());
+    /// std::fs::remove_file(&executable).unwrap();
+    /// ```
+    pub fn with_wide_output(mut self, wide_output: WideOutput) -> Self {
+        self.wide_output = wide_output;
+        self
+    }
+
+    /// Wrap the pointer around the ends of the tape. Each move then also compares the pointer
+    /// against the end of the tape.
+    pub fn with_pointer_wrap(mut self, pointer_wrap: bool) -> Self {
+        self.pointer_wrap = pointer_wrap;
+        self
+    }
+
+    /// Export the generated function as `entry` instead of [`DEFAULT_ENTRY`], e.g. to link the
+    /// object into a larger program that calls the brainfuck program as `int entry(void)`. It
+    /// must be a C identifier and may not clash with the libc functions the object imports.
+    pub fn with_entry(mut self, entry: String) -> Self {
+        self.entry = entry;
+        self
+    }
+
+    /// How much Cranelift optimizes the generated machine code. The IR is verified before it's
+    /// optimized at every level.
+    pub fn with_codegen_opt(mut self, codegen_opt: CodegenOpt) -> Self {
+        self.codegen_opt = codegen_opt;
+        self
+    }
+
+    /// Start the pointer in the middle of the tape, so that programs can move up to half of the
+    /// tape's size left of the starting cell. Like moving past the ends of the tape, moving
+    /// further is only checked with [`Compiler::with_check_bounds`].
+    pub fn with_signed_tape(mut self, signed_tape: bool) -> Self {
+        self.signed_tape = signed_tape;
+        self
+    }
+
+    /// Whether to write an object file or to link it into an executable, see [`link`].
+    pub fn with_emit(mut self, emit: Emit) -> Self {
+        self.emit = emit;
+        self
+    }
+
+    /// Whether to link a position-independent executable, or to leave that to the linker's
+    /// default if `None`. The object is position-independent either way.
+    ///
+    /// ```
+    /// use brainlift::{cli::Emit, Compiler, EofBehaviour, Parser};
+    ///
+    /// let program = Parser::new("+.").parse().unwrap();
+    /// let executable = std::env::temp_dir().join(format!("pie-{}", std::process::id()));
+    /// let elf_type = |pie| {
+    ///     Compiler::new(10, EofBehaviour::Ignore)
+    ///         .with_emit(Emit::Exe)
+    ///         .with_pie(Some(pie))
+    ///         .compile(&program, executable.clone())
+    ///         .unwrap();
+    ///     let header = std::process::Command::new("readelf")
+    ///         .arg("--file-header")
+    ///         .arg(&executable)
+    ///         .output()
+    ///         .unwrap();
+    ///     let header = String::from_utf8(header.stdout).unwrap();
+    ///     let line = header.lines().find(|line| line.trim().starts_with("Type:")).unwrap();
+    ///     line.split_whitespace().nth(1).unwrap().to_owned()
+    /// };
+    ///
+    /// assert_eq!(elf_type(true), "DYN");
+    /// assert_eq!(elf_type(false), "EXEC");
+    /// std::fs::remove_file(&executable).unwrap();
+    /// ```
+    pub fn with_pie(mut self, pie: Option<bool>) -> Self {
+        self.pie = pie;
+        self
+    }
+
+    /// Use the given calling convention for the entry function instead of the target's default.
+    ///
+    /// ```
+    /// use brainlift::{Compiler, EofBehaviour, Parser, cli::{CallConvention, Emit}};
+    ///
+    /// let program = Parser::new("+.").parse().unwrap();
+    /// let clif = std::env::temp_dir().join(format!("call-conv-{}.clif", std::process::id()));
+    /// let signature = |call_conv| {
+    ///     Compiler::new(10, EofBehaviour::Ignore)
+    ///         .with_emit(Emit::Clif)
+    ///         .with_call_conv(call_conv)
+    ///         .compile(&program, clif.clone())
+    ///         .unwrap();
+    ///     let clif = std::fs::read_to_string(&clif).unwrap();
+    ///     clif.lines().find(|line| line.starts_with("function")).unwrap().to_owned()
+    /// };
+    ///
+    /// let windows = signature(Some(CallConvention::WindowsFastcall));
+    /// assert_eq!(windows, "function u0:0() -> i32 windows_fastcall {");
+    /// assert!(!signature(None).contains("windows_fastcall"));
+    /// std::fs::remove_file(&clif).unwrap();
+    /// ```
+    pub fn with_call_conv(mut self, call_conv: Option<CallConvention>) -> Self {
+        self.call_conv = call_conv;
+        self
+    }
+
+    /// Split the program into chunks of `chunk_size` top-level instructions, each compiled as a
+    /// separate function that tail-calls the next one. This keeps the individual functions small,
+    /// which speeds up compilation of huge programs at the cost of a call per chunk.
+    pub fn with_chunk_size(mut self, chunk_size: Option<usize>) -> Self {
+        self.chunk_size = chunk_size;
+        self
+    }
+
+    /// Reuse objects from previous compilations of the same program with the same settings,
+    /// stored in `cache_dir`. Each object is stored with the settings it was compiled with, which
+    /// have to match exactly to reuse it. The cache isn't used for size reports and call graphs,
+    /// which are collected while generating the code.
+    ///
+    /// ```
+    /// use brainlift::{Compiler, EofBehaviour, Parser};
+    ///
+    /// let dir = std::env::temp_dir().join(format!("cache-{}", std::process::id()));
+    /// let object = dir.join("program.o");
+    /// std::fs::create_dir_all(&dir).unwrap();
+    /// let program = Parser::new("+.").parse().unwrap();
+    /// let compile = || {
+    ///     Compiler::new(10, EofBehaviour::Ignore)
+    ///         .with_cache_dir(Some(dir.join("cache")))
+    ///         .compile(&program, object.clone())
+    ///         .unwrap();
+    ///     std::fs::read(&object).unwrap()
+    /// };
+    ///
+    /// let compiled = compile();
+    /// let cached: Vec<_> = std::fs::read_dir(dir.join("cache"))
+    ///     .unwrap()
+    ///     .map(|entry| entry.unwrap().path())
+    ///     .collect();
+    /// assert_eq!(cached.len(), 2);
+    ///
+    /// // a hit copies the cached object, so replacing it shows in the output
+    /// let cached_object = cached.iter().find(|path| path.extension().unwrap() == "o").unwrap();
+    /// std::fs::write(cached_object, "cached").unwrap();
+    /// assert_eq!(compile(), b"cached");
+    ///
+    /// // an object stored with other settings is compiled again
+    /// let key = cached.iter().find(|path| path.extension().unwrap() == "key").unwrap();
+    /// std::fs::write(key, "other settings").unwrap();
+    /// assert_eq!(compile(), compiled);
+    /// std::fs::remove_dir_all(&dir).unwrap();
+    /// ```
+    pub fn with_cache_dir(mut self, cache_dir: Option<PathBuf>) -> Self {
+        self.cache_dir = cache_dir;
+        self
+    }
+
+    pub fn with_verbose(mut self, verbose: bool) -> Self {
+        self.verbose = verbose;
+        self
+    }
+
+    /// Print the sizes of the generated functions and of the object's sections to stderr.
+    pub fn with_size_report(mut self, size_report: bool) -> Self {
+        self.size_report = size_report;
+        self
+    }
+
+    /// Print the symbols the object imports, i.e. what it has to be linked against, and the ones
+    /// it exports to stdout.
+    pub fn with_list_symbols(mut self, list_symbols: bool) -> Self {
+        self.list_symbols = list_symbols;
+        self
+    }
+
+    /// Write a graph of which generated function calls which, in DOT format, to the given file
+    /// or, if none is given, to stdout. Most interesting together with chunking.
+    pub fn with_callgraph(mut self, callgraph: Option<Option<PathBuf>>) -> Self {
+        self.callgraph = callgraph;
+        self
+    }
+
+    pub fn compile(mut self, program: &Program, output_file: PathBuf) -> Result<(), CompileError> {
+        if !is_identifier(&self.entry) || IMPORTED_FUNCTION_SYMBOLS.contains(&self.entry.as_str()) {
+            return Err(CompileError::InvalidEntry(self.entry));
+        }
+
+        match self.emit {
+            Emit::Obj => self.emit_object(program, &output_file)?,
+            Emit::Clif => {
+                self.clif = Some(String::new());
+                self.build_object(program, &output_file)?;
+                let clif = self.clif.take().unwrap_or_default();
+                if output_file == Path::new("-") {
+                    print!("{clif}");
+                    return Ok(());
+                }
+                fs::write(&output_file, clif)
+                    .map_err(|e| CompileError::Io(output_file.clone(), e))?;
+            }
+            Emit::Exe => {
+                let object_file =
+                    create_temp_object().map_err(|e| CompileError::Io(env::temp_dir(), e))?;
+                let result = self
+                    .emit_object(program, &object_file)
+                    .and_then(|()| link(&object_file, &output_file, self.pie));
+                let _ = fs::remove_file(&object_file);
+                result?;
+            }
+        }
+
+        // stdout may carry a report like the call graph
+        eprintln!("finished compilation of {output_file:?}");
+        Ok(())
+    }
+
+    /// Writes the object file for `program` to `output_file`, or copies it from the cache.
+    fn emit_object(&mut self, program: &Program, output_file: &Path) -> Result<(), CompileError> {
+        // the reports are collected while generating the code, so it can't be skipped
+        let reports = self.size_report || self.callgraph.is_some();
+        if reports && self.cache_dir.is_some() && self.verbose {
+            eprintln!("not using the cache, as size reports and call graphs need a compilation");
+        }
+        let key = self.cache_key(program);
+        let cache = self.cache_dir.as_ref().filter(|_| !reports).map(|dir| {
+            let name = format!("{:016x}", stable_hash(&key));
+            (
+                dir.join(format!("{name}.o")),
+                dir.join(format!("{name}.key")),
+            )
+        });
+
+        // a different key under the same hash is a collision and compiled again
+        if let Some((cached_file, key_file)) = &cache
+            && fs::read_to_string(key_file).is_ok_and(|stored| stored == key)
+            && let Ok(bytes) = fs::read(cached_file)
+        {
+            if self.verbose {
+                eprintln!("cache hit: reusing {cached_file:?}");
+            }
+            fs::write(output_file, &bytes).map_err(|e| CompileError::Io(output_file.into(), e))?;
+            if self.list_symbols {
+                print_symbols(&bytes);
+            }
+            return Ok(());
+        }
+
+        let bytes = self.build_object(program, output_file)?;
+        fs::write(output_file, &bytes).map_err(|e| CompileError::Io(output_file.into(), e))?;
+
+        if let Some((cached_file, key_file)) = &cache {
+            if self.verbose {
+                eprintln!("cache miss: storing object as {cached_file:?}");
+            }
+            // the key is written last, so that an interrupted store is never reused
+            let stored = fs::create_dir_all(cached_file.parent().unwrap())
+                .and_then(|_| fs::write(cached_file, &bytes))
+                .and_then(|_| fs::write(key_file, &key));
+            if let Err(e) = stored {
+                eprintln!("warning: failed to store object in cache: {e}");
+            }
+        }
+
+        if self.size_report {
+            self.print_size_report(&bytes);
+        }
+
+        if self.list_symbols {
+            print_symbols(&bytes);
+        }
+
+        if let Some(callgraph) = &self.callgraph {
+            self.write_callgraph(callgraph.as_deref()).map_err(|e| {
+                let path = callgraph.as_deref().unwrap_or(Path::new("-"));
+                CompileError::Io(path.into(), e)
+            })?;
+        }
+
+        Ok(())
+    }
+
+    /// Generates the functions for `program` and returns the object containing them. With
+    /// [`Emit::Clif`], their IR is collected as well.
+    fn build_object(
+        &mut self,
+        program: &Program,
+        output_file: &Path,
+    ) -> Result<Vec<u8>, CompileError> {
         let isa = {
             let mut builder = settings::builder();
-            builder.set("opt_level", "none").unwrap();
+            builder
+                .set("opt_level", self.codegen_opt.setting())
+                .unwrap();
             builder.enable("is_pic").unwrap();
+            if self.chunk_size.is_some() {
+                // required by Cranelift's implementation of tail calls
+                builder.enable("preserve_frame_pointers").unwrap();
+            }
             let flags = settings::Flags::new(builder);
-            isa::lookup_by_name(TARGET_TRIPLE)
-                .unwrap()
+            isa::lookup_by_name(&self.target)
+                .map_err(|_| CompileError::UnsupportedTarget(self.target.clone()))?
                 .finish(flags)
                 .unwrap()
         };
@@ -52,95 +662,466 @@ impl Compiler {
         };
 
         let _main_declaration = {
-            let sig = Signature {
-                call_conv: module.isa().default_call_conv(),
-                params: vec![],
-                returns: vec![AbiParam::new(types::I32)],
-            };
+            let sig = self.entry_signature(&module);
 
             module
-                .declare_function(ENTRYPOINT_FUNCTION_SYMBOL, Linkage::Export, &sig)
+                .declare_function(&self.entry, Linkage::Export, &sig)
                 .unwrap()
         };
 
         self.declare_external_functions(&mut module);
 
-        self.main_function(&mut module, program);
+        let chunks = match self.chunk_size {
+            Some(chunk_size) => program.instructions.chunks(chunk_size).collect(),
+            None => Vec::new(),
+        };
+        self.declare_chunk_functions(&mut module, chunks.len());
 
-        let product = module.finish();
+        self.main_function(&mut module, program, chunks.len())?;
 
-        {
-            let bytes = product.emit().unwrap();
+        for (index, chunk) in chunks.iter().enumerate() {
+            self.chunk_function(&mut module, index, chunk, index + 1 == chunks.len())?;
+        }
+
+        Ok(module.finish().emit().unwrap())
+    }
 
-            let mut f = File::create(&output_file).unwrap();
-            f.write_all(&bytes).unwrap();
+    /// Describes everything that influences the emitted object: the parsed instructions, the
+    /// compiler settings and the brainlift version. Comments in the source don't affect the key.
+    fn cache_key(&self, program: &Program) -> String {
+        [
+            format!("brainlift {}", env!("CARGO_PKG_VERSION")),
+            format!("target {}", self.target),
+            format!("entry {}", self.entry),
+            format!("codegen-opt {:?}", self.codegen_opt),
+            format!("array-size {}", self.max_array_size),
+            format!("eof-behaviour {:?}", self.eof_behaviour),
+            format!("check-output {}", self.check_output),
+            format!("check-bounds {}", self.check_bounds),
+            format!("interruptible {}", self.interruptible),
+            format!("free-tape {}", self.free_tape),
+            format!("tape-storage {:?}", self.tape_storage),
+            format!("cell-size {:?}", self.cell_size),
+            format!("cell-endian {:?}", self.cell_endian),
+            format!("wide-output {:?}", self.wide_output),
+            format!("pointer-wrap {}", self.pointer_wrap),
+            format!("signed-tape {}", self.signed_tape),
+            format!("call-conv {:?}", self.call_conv),
+            format!("chunk-size {:?}", self.chunk_size),
+            format!("instructions {:?}", program.instructions),
+        ]
+        .join("\n")
+    }
+
+    fn print_size_report(&self, bytes: &[u8]) {
+        eprintln!("{:<16} {:>10}", "function", "bytes");
+        for (name, size) in &self.function_sizes {
+            eprintln!("{name:<16} {size:>10}");
+        }
+        let total: u32 = self.function_sizes.iter().map(|(_, size)| size).sum();
+        eprintln!("{:<16} {total:>10}", "total code");
 
-            println!("finished compilation of {output_file:?}");
+        eprintln!();
+        eprintln!("{:<16} {:>10}", "section", "bytes");
+        let file = object::File::parse(bytes).expect("emitted object should be readable");
+        for section in file.sections() {
+            if matches!(
+                section.kind(),
+                SectionKind::Text
+                    | SectionKind::Data
+                    | SectionKind::ReadOnlyData
+                    | SectionKind::UninitializedData
+            ) {
+                let name = section.name().unwrap_or("?");
+                eprintln!("{name:<16} {:>10}", section.size());
+            }
         }
     }
 
-    fn main_function(&mut self, module: &mut ObjectModule, program: &Program) {
+    fn write_callgraph(&self, file: Option<&Path>) -> io::Result<()> {
+        let mut out: Box<dyn Write> = match file {
+            Some(file) => Box::new(File::create(file)?),
+            None => Box::new(io::stdout()),
+        };
+
+        writeln!(out, "digraph callgraph {{")?;
+        for (name, _) in &self.function_sizes {
+            writeln!(out, "    \"{name}\";")?;
+        }
+        for (caller, callee) in &self.calls {
+            writeln!(out, "    \"{caller}\" -> \"{callee}\";")?;
+        }
+        writeln!(out, "}}")
+    }
+
+    /// Defines the entry function. If the program was split into `chunk_count` chunks, it only
+    /// calls the first chunk instead of containing the program itself.
+    fn main_function(
+        &mut self,
+        module: &mut ObjectModule,
+        program: &Program,
+        chunk_count: usize,
+    ) -> Result<(), CompileError> {
         let mut ctx = codegen::Context::new();
         let mut fctx = FunctionBuilderContext::new();
 
         let mut builder = FunctionBuilder::new(&mut ctx.func, &mut fctx);
-        builder.func.signature = Signature {
-            call_conv: module.isa().default_call_conv(),
-            params: vec![],
-            returns: vec![AbiParam::new(types::I32)],
-        };
+        builder.func.signature = self.entry_signature(module);
 
         let block0 = builder.create_block();
         builder.switch_to_block(block0);
         builder.seal_block(block0);
 
-        let calloc =
-            module.declare_func_in_func(self.func_id(module, CALLOC_FUNCTION_SYMBOL), builder.func);
-        let array_ptr = {
-            let size_t = Type::int(module.target_config().pointer_bits() as u16).unwrap();
+        let array_ptr = match self.tape_storage {
+            TapeStorage::Heap => {
+                let calloc = module.declare_func_in_func(
+                    self.func_id(module, CALLOC_FUNCTION_SYMBOL),
+                    builder.func,
+                );
+                let size_t = Type::int(module.target_config().pointer_bits() as u16).unwrap();
+
+                let n = builder.ins().iconst(size_t, self.max_array_size as i64);
+                let size = builder.ins().iconst(size_t, self.cell_size.bytes() as i64);
+                let call = builder.ins().call(calloc, &[n, size]);
+                builder.inst_results(call)[0]
+            }
+            TapeStorage::Stack => {
+                let ptr_t = module.target_config().pointer_type();
+                let slot = builder.create_sized_stack_slot(StackSlotData::new(
+                    StackSlotKind::ExplicitSlot,
+                    self.tape_bytes() as u32,
+                    4,
+                ));
+                let array_ptr = builder.ins().stack_addr(ptr_t, slot, 0);
+
+                let zero = builder.ins().iconst(types::I8, 0);
+                let n = builder.ins().iconst(ptr_t, self.tape_bytes() as i64);
+                builder.call_memset(module.target_config(), array_ptr, zero, n);
+                array_ptr
+            }
+        };
+
+        let putchar = module
+            .declare_func_in_func(self.func_id(module, PUTCHAR_FUNCTION_SYMBOL), builder.func);
+        let getchar = module
+            .declare_func_in_func(self.func_id(module, GETCHAR_FUNCTION_SYMBOL), builder.func);
+
+        let should_continue = self.interruptible.then(|| {
+            module.declare_func_in_func(
+                self.func_id(module, SHOULD_CONTINUE_FUNCTION_SYMBOL),
+                builder.func,
+            )
+        });
+
+        // taken when writing the output fails or the program is interrupted
+        let abort_block = (self.check_output || self.interruptible).then(|| builder.create_block());
 
-            let n = builder.ins().iconst(size_t, self.max_array_size as i64);
-            let size = builder.ins().iconst(size_t, 1);
-            let call = builder.ins().call(calloc, &[n, size]);
-            builder.inst_results(call)[0]
+        let start_ptr = if self.signed_tape {
+            let middle = self.max_array_size / 2 * self.cell_size.bytes();
+            builder.ins().iadd_imm(array_ptr, middle as i64)
+        } else {
+            array_ptr
         };
 
+        if self.chunk_size.is_some() {
+            if chunk_count > 0 {
+                let chunk = module.declare_func_in_func(
+                    self.func_id(module, &self.chunk_symbol(0)),
+                    builder.func,
+                );
+                let args = if self.passes_tape_start() {
+                    vec![start_ptr, array_ptr]
+                } else {
+                    vec![start_ptr]
+                };
+                let call = builder.ins().call(chunk, &args);
+
+                // chunks return a null pointer if writing the output failed or they were
+                // interrupted
+                if let Some(abort_block) = abort_block {
+                    let result = builder.inst_results(call)[0];
+                    let failed = builder.ins().icmp_imm(IntCC::Equal, result, 0);
+
+                    let next_block = builder.create_block();
+                    builder
+                        .ins()
+                        .brif(failed, abort_block, &[], next_block, &[]);
+                    builder.seal_block(next_block);
+                    builder.switch_to_block(next_block);
+                }
+            }
+        } else {
+            let swap_cell_bytes = self.swap_cell_bytes(module.isa().endianness());
+            let mut e = Emitter {
+                module,
+                builder: &mut builder,
+                putchar,
+                getchar,
+                array_ptr: start_ptr,
+                tape_start: self.passes_tape_start().then_some(array_ptr),
+                check_bounds: self.checks_bounds(),
+                tape_bytes: self.tape_bytes() as i64,
+                cell_type: self.cell_type(),
+                swap_cell_bytes,
+                wide_output: self.wide_output,
+                eof_behaviour: self.eof_behaviour,
+                should_continue,
+                abort_block,
+            };
+
+            for instruction in &program.instructions {
+                e.emit(instruction);
+            }
+        }
+
+        if self.check_output
+            && let Some(abort_block) = abort_block
+        {
+            let fflush = module
+                .declare_func_in_func(self.func_id(module, FFLUSH_FUNCTION_SYMBOL), builder.func);
+            let ptr_t = module.target_config().pointer_type();
+
+            let all_streams = builder.ins().iconst(ptr_t, 0);
+            let call = builder.ins().call(fflush, &[all_streams]);
+            let result = builder.inst_results(call)[0];
+            let failed = builder.ins().icmp_imm(IntCC::NotEqual, result, 0);
+
+            let exit_block = builder.create_block();
+            builder
+                .ins()
+                .brif(failed, abort_block, &[], exit_block, &[]);
+            builder.seal_block(exit_block);
+            builder.switch_to_block(exit_block);
+        }
+
+        let free = self.frees_tape().then(|| {
+            module.declare_func_in_func(self.func_id(module, FREE_FUNCTION_SYMBOL), builder.func)
+        });
+        if let Some(free) = free {
+            builder.ins().call(free, &[array_ptr]);
+        }
+
+        let zero = builder.ins().iconst(types::I32, 0);
+        builder.ins().return_(&[zero]);
+
+        if let Some(abort_block) = abort_block {
+            builder.seal_block(abort_block);
+            builder.switch_to_block(abort_block);
+
+            if let Some(free) = free {
+                builder.ins().call(free, &[array_ptr]);
+            }
+
+            let one = builder.ins().iconst(types::I32, 1);
+            builder.ins().return_(&[one]);
+        }
+
+        builder.finalize();
+
+        let entry = self.entry.clone();
+        self.define_function(module, &entry, &mut ctx)
+    }
+
+    /// Defines the function for the chunk at `index`, which takes and returns the array pointer
+    /// and tail-calls the next chunk unless it is the last one. With pointer wrapping or bounds
+    /// checks, it also takes the start of the tape.
+    fn chunk_function(
+        &mut self,
+        module: &mut ObjectModule,
+        index: usize,
+        instructions: &[Instruction],
+        last: bool,
+    ) -> Result<(), CompileError> {
+        let mut ctx = codegen::Context::new();
+        let mut fctx = FunctionBuilderContext::new();
+
+        let mut builder = FunctionBuilder::new(&mut ctx.func, &mut fctx);
+        builder.func.signature = chunk_signature(module, self.passes_tape_start());
+
+        let block0 = builder.create_block();
+        builder.append_block_params_for_function_params(block0);
+        builder.switch_to_block(block0);
+        builder.seal_block(block0);
+
         let putchar = module
             .declare_func_in_func(self.func_id(module, PUTCHAR_FUNCTION_SYMBOL), builder.func);
         let getchar = module
             .declare_func_in_func(self.func_id(module, GETCHAR_FUNCTION_SYMBOL), builder.func);
 
+        let should_continue = self.interruptible.then(|| {
+            module.declare_func_in_func(
+                self.func_id(module, SHOULD_CONTINUE_FUNCTION_SYMBOL),
+                builder.func,
+            )
+        });
+
+        // taken when writing the output fails or the program is interrupted
+        let abort_block = (self.check_output || self.interruptible).then(|| builder.create_block());
+
+        // with pointer wrapping or bounds checks, chunks are also passed the start of the tape
+        let tape_start = self
+            .passes_tape_start()
+            .then(|| builder.block_params(block0)[1]);
+        let swap_cell_bytes = self.swap_cell_bytes(module.isa().endianness());
+
         let mut e = Emitter {
             module,
+            array_ptr: builder.block_params(block0)[0],
             builder: &mut builder,
             putchar,
             getchar,
-            array_ptr,
+            tape_start,
+            check_bounds: self.checks_bounds(),
+            tape_bytes: self.tape_bytes() as i64,
+            cell_type: self.cell_type(),
+            swap_cell_bytes,
+            wide_output: self.wide_output,
             eof_behaviour: self.eof_behaviour,
+            should_continue,
+            abort_block,
         };
 
-        for instruction in &program.instructions {
+        for instruction in instructions {
             e.emit(instruction);
         }
+        let array_ptr = e.array_ptr;
 
-        let free =
-            module.declare_func_in_func(self.func_id(module, FREE_FUNCTION_SYMBOL), builder.func);
-        builder.ins().call(free, &[array_ptr]);
+        if last {
+            builder.ins().return_(&[array_ptr]);
+        } else {
+            let next = module.declare_func_in_func(
+                self.func_id(module, &self.chunk_symbol(index + 1)),
+                builder.func,
+            );
+            let args: Vec<_> = std::iter::once(array_ptr).chain(tape_start).collect();
+            builder.ins().return_call(next, &args);
+        }
 
-        let zero = builder.ins().iconst(types::I32, 0);
-        builder.ins().return_(&[zero]);
+        if let Some(abort_block) = abort_block {
+            builder.seal_block(abort_block);
+            builder.switch_to_block(abort_block);
 
-        codegen::verify_function(builder.func, module.isa()).expect("verification should succeed");
+            let ptr_t = module.target_config().pointer_type();
+            let null = builder.ins().iconst(ptr_t, 0);
+            builder.ins().return_(&[null]);
+        }
 
         builder.finalize();
 
+        self.define_function(module, &self.chunk_symbol(index), &mut ctx)
+    }
+
+    fn define_function(
+        &mut self,
+        module: &mut ObjectModule,
+        name: &str,
+        ctx: &mut codegen::Context,
+    ) -> Result<(), CompileError> {
+        codegen::verify_function(&ctx.func, module.isa()).map_err(|errors| {
+            let diagnostic = pretty_verifier_error(&ctx.func, None, errors);
+            CompileError::Verification(diagnostic)
+        })?;
+
+        // before compiling, which optimizes the function in place
+        if let Some(clif) = &mut self.clif {
+            clif.push_str(&format!("; {name}\n{}\n", ctx.func));
+        }
+
         module
-            .define_function(self.func_id(module, ENTRYPOINT_FUNCTION_SYMBOL), &mut ctx)
+            .define_function(self.func_id(module, name), ctx)
             .unwrap();
 
-        // println!("fn {ENTRYPOINT_FUNCTION_SYMBOL}:\n{}", &ctx.func);
+        let size = ctx.compiled_code().unwrap().code_info().total_size;
+        self.function_sizes.push((name.to_string(), size));
+        self.record_calls(module, name, &ctx.func);
 
         ctx.clear();
+
+        Ok(())
+    }
+
+    /// Records the callees of all (tail) calls in `func`, each only once.
+    fn record_calls(&mut self, module: &ObjectModule, name: &str, func: &codegen::ir::Function) {
+        for block in func.layout.blocks() {
+            for inst in func.layout.block_insts(block) {
+                let InstructionData::Call { func_ref, .. } = func.dfg.insts[inst] else {
+                    continue;
+                };
+                let ExternalName::User(user_name) = func.dfg.ext_funcs[func_ref].name else {
+                    continue;
+                };
+
+                let func_id = FuncId::from_u32(func.params.user_named_funcs()[user_name].index);
+                let callee = module.declarations().get_function_decl(func_id);
+                let call = (name.to_string(), callee.linkage_name(func_id).into_owned());
+                if !self.calls.contains(&call) {
+                    self.calls.push(call);
+                }
+            }
+        }
+    }
+
+    fn declare_chunk_functions(&mut self, module: &mut ObjectModule, chunk_count: usize) {
+        let sig = chunk_signature(module, self.passes_tape_start());
+
+        for index in 0..chunk_count {
+            module
+                .declare_function(&self.chunk_symbol(index), Linkage::Local, &sig)
+                .unwrap();
+        }
+    }
+
+    /// Size of the tape in bytes.
+    fn tape_bytes(&self) -> usize {
+        self.max_array_size * self.cell_size.bytes()
+    }
+
+    fn cell_type(&self) -> Type {
+        match self.cell_size {
+            CellSize::Bits8 => types::I8,
+            CellSize::Bits16 => types::I16,
+            CellSize::Bits32 => types::I32,
+        }
+    }
+
+    /// Whether cells are stored in the other byte order than the target's, which Cranelift can
+    /// only load and store natively.
+    fn swap_cell_bytes(&self, native: Endianness) -> bool {
+        let order = match self.cell_endian {
+            CellEndian::Little => Endianness::Little,
+            CellEndian::Big => Endianness::Big,
+        };
+        self.cell_size != CellSize::Bits8 && order != native
+    }
+
+    /// Whether moves are checked against the ends of the tape, which a wrapping pointer never
+    /// leaves.
+    fn checks_bounds(&self) -> bool {
+        self.check_bounds && !self.pointer_wrap
+    }
+
+    /// Whether the emitted code needs the start of the tape besides the pointer.
+    fn passes_tape_start(&self) -> bool {
+        self.pointer_wrap || self.checks_bounds()
+    }
+
+    fn frees_tape(&self) -> bool {
+        self.free_tape && self.tape_storage == TapeStorage::Heap
+    }
+
+    fn entry_signature(&self, module: &ObjectModule) -> Signature {
+        let call_conv = match self.call_conv {
+            None => module.isa().default_call_conv(),
+            Some(CallConvention::SystemV) => CallConv::SystemV,
+            Some(CallConvention::WindowsFastcall) => CallConv::WindowsFastcall,
+            Some(CallConvention::Tail) => CallConv::Tail,
+        };
+
+        Signature {
+            call_conv,
+            params: vec![],
+            returns: vec![AbiParam::new(types::I32)],
+        }
     }
 
     fn declare_external_functions(&mut self, module: &mut ObjectModule) {
@@ -171,7 +1152,7 @@ impl Compiler {
         let size_t = Type::int(module.target_config().pointer_bits() as u16).unwrap();
         let ptr_t = module.target_config().pointer_type();
 
-        let _calloc_declaration = {
+        if self.tape_storage == TapeStorage::Heap {
             let sig = Signature {
                 params: vec![AbiParam::new(size_t), AbiParam::new(size_t)],
                 returns: vec![AbiParam::new(ptr_t)],
@@ -180,10 +1161,10 @@ impl Compiler {
 
             module
                 .declare_function(CALLOC_FUNCTION_SYMBOL, Linkage::Import, &sig)
-                .unwrap()
-        };
+                .unwrap();
+        }
 
-        let _free_declaration = {
+        if self.frees_tape() {
             let sig = Signature {
                 params: vec![AbiParam::new(ptr_t)],
                 returns: vec![],
@@ -192,8 +1173,36 @@ impl Compiler {
 
             module
                 .declare_function(FREE_FUNCTION_SYMBOL, Linkage::Import, &sig)
-                .unwrap()
-        };
+                .unwrap();
+        }
+
+        if self.check_output {
+            let sig = Signature {
+                params: vec![AbiParam::new(ptr_t)],
+                returns: vec![AbiParam::new(types::I32)],
+                call_conv: module.isa().default_call_conv(),
+            };
+
+            module
+                .declare_function(FFLUSH_FUNCTION_SYMBOL, Linkage::Import, &sig)
+                .unwrap();
+        }
+
+        if self.interruptible {
+            let sig = Signature {
+                params: vec![],
+                returns: vec![AbiParam::new(types::I32)],
+                call_conv: module.isa().default_call_conv(),
+            };
+
+            module
+                .declare_function(SHOULD_CONTINUE_FUNCTION_SYMBOL, Linkage::Import, &sig)
+                .unwrap();
+        }
+    }
+
+    fn chunk_symbol(&self, index: usize) -> String {
+        format!("{}_chunk{index}", self.entry)
     }
 
     fn func_id(&self, module: &ObjectModule, name: &str) -> FuncId {
@@ -204,13 +1213,200 @@ impl Compiler {
     }
 }
 
+/// Links the object file `object` with libc into the executable `output`, using the C compiler
+/// named by `$CC` or else [`DEFAULT_LINKER`]. The executable is position-independent if `pie` is
+/// `Some(true)`, and not if it is `Some(false)`. The linker's own diagnostics go to stderr.
+pub fn link(object: &Path, output: &Path, pie: Option<bool>) -> Result<(), CompileError> {
+    let linker = env::var_os("CC").unwrap_or_else(|| OsString::from(DEFAULT_LINKER));
+    let mut command = Command::new(&linker);
+    match pie {
+        Some(true) => command.arg("-pie"),
+        Some(false) => command.arg("-no-pie"),
+        None => &mut command,
+    };
+    let status = command
+        .arg(object)
+        .arg("-o")
+        .arg(output)
+        .status()
+        .map_err(|e| CompileError::Link(format!("failed to run {linker:?}: {e}")))?;
+    if !status.success() {
+        return Err(CompileError::Link(format!(
+            "{linker:?} exited with {status}"
+        )));
+    }
+    Ok(())
+}
+
+/// Prints the undefined and the global symbols of an object, as seen by the linker.
+fn print_symbols(bytes: &[u8]) {
+    let (imports, exports) = object_symbols(bytes);
+    for name in imports {
+        println!("import {name}");
+    }
+    for name in exports {
+        println!("export {name}");
+    }
+}
+
+/// The undefined and the global symbols of an object, each sorted by name, as listed by
+/// [`Compiler::with_list_symbols`].
+///
+/// ```
+/// use brainlift::{Compiler, EofBehaviour, Parser, cli::TapeStorage, compiler::object_symbols};
+///
+/// let object = std::env::temp_dir().join(format!("symbols-{}.o", std::process::id()));
+/// let program = Parser::new("+[-]").parse().unwrap();
+/// let symbols = |compiler: Compiler| {
+///     compiler.compile(&program, object.clone()).unwrap();
+///     object_symbols(&std::fs::read(&object).unwrap())
+/// };
+///
+/// let (imports, exports) = symbols(Compiler::new(10, EofBehaviour::Ignore));
+/// assert!(imports.iter().any(|name| name == "calloc"));
+/// assert!(imports.iter().any(|name| name == "free"));
+/// assert!(!imports.iter().any(|name| name == "should_continue"));
+/// assert_eq!(exports, ["main"]);
+///
+/// let (imports, _) = symbols(Compiler::new(10, EofBehaviour::Ignore).with_free_tape(false));
+/// assert!(imports.iter().any(|name| name == "calloc"));
+/// assert!(!imports.iter().any(|name| name == "free"));
+///
+/// let (imports, _) = symbols(
+///     Compiler::new(10, EofBehaviour::Ignore)
+///         .with_tape_storage(TapeStorage::Stack)
+///         .with_interruptible(true),
+/// );
+/// assert!(!imports.iter().any(|name| name == "calloc"));
+/// assert!(imports.iter().any(|name| name == "should_continue"));
+/// std::fs::remove_file(&object).unwrap();
+/// ```
+pub fn object_symbols(bytes: &[u8]) -> (Vec<String>, Vec<String>) {
+    let file = object::File::parse(bytes).expect("emitted object should be readable");
+
+    let mut imports = Vec::new();
+    let mut exports = Vec::new();
+    for symbol in file.symbols() {
+        let Ok(name) = symbol.name() else {
+            continue;
+        };
+        if name.is_empty() {
+            continue;
+        }
+
+        if symbol.is_undefined() {
+            imports.push(name.to_owned());
+        } else if symbol.is_global() {
+            exports.push(name.to_owned());
+        }
+    }
+    imports.sort_unstable();
+    exports.sort_unstable();
+    (imports, exports)
+}
+
+/// Whether `name` can be used as a symbol by C code.
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Creates an empty file for the object that is linked into an executable. It gets a random name
+/// and is created exclusively, so that no file or symlink another user put in the shared
+/// temporary directory is written to instead.
+fn create_temp_object() -> io::Result<PathBuf> {
+    loop {
+        let random = RandomState::new().build_hasher().finish();
+        let name = format!("brainlift-{}-{random:016x}.o", process::id());
+        let path = env::temp_dir().join(name);
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(_) => return Ok(path),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// A hash of `text` that doesn't change between builds or toolchains (FNV-1a), used to name
+/// cached objects.
+fn stable_hash(text: &str) -> u64 {
+    text.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+fn chunk_signature(module: &ObjectModule, passes_tape_start: bool) -> Signature {
+    let ptr_t = module.target_config().pointer_type();
+    let params = if passes_tape_start { 2 } else { 1 };
+
+    Signature {
+        call_conv: CallConv::Tail,
+        params: vec![AbiParam::new(ptr_t); params],
+        returns: vec![AbiParam::new(ptr_t)],
+    }
+}
+
+#[derive(Debug)]
+pub enum CompileError {
+    /// The generated IR was rejected by the Cranelift verifier, which indicates a bug in brainlift.
+    Verification(String),
+    /// The object couldn't be linked into an executable.
+    Link(String),
+    /// There is no Cranelift backend for the architecture in this build.
+    UnsupportedTarget(String),
+    /// The entry function can't be exported under this name.
+    InvalidEntry(String),
+    /// The output couldn't be written to the given file.
+    Io(PathBuf, io::Error),
+}
+
+impl std::fmt::Display for CompileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CompileError::Verification(diagnostic) => {
+                write!(f, "generated code failed verification:\n{diagnostic}")
+            }
+            CompileError::Link(message) => write!(f, "failed to link executable: {message}"),
+            CompileError::InvalidEntry(entry) => write!(
+                f,
+                "invalid entry name `{entry}`, it must be a C identifier other than {}",
+                IMPORTED_FUNCTION_SYMBOLS.join(", ")
+            ),
+            CompileError::UnsupportedTarget(target) => write!(
+                f,
+                "unsupported target `{target}`, supported are: {}",
+                supported_targets().join(", ")
+            ),
+            CompileError::Io(path, e) => write!(f, "failed to write {path:?}: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for CompileError {}
+
 struct Emitter<'a, 'b> {
     module: &'a mut ObjectModule,
     builder: &'a mut FunctionBuilder<'b>,
     putchar: FuncRef,
     getchar: FuncRef,
     array_ptr: Value,
+    /// Start of the tape, if the pointer wraps around its ends or `check_bounds` is set.
+    tape_start: Option<Value>,
+    /// Trap when an address leaves the tape.
+    check_bounds: bool,
+    tape_bytes: i64,
+    cell_type: Type,
+    /// Byte-swap cells on every load and store, if they are stored in the other byte order than
+    /// the target's.
+    swap_cell_bytes: bool,
+    wide_output: WideOutput,
     eof_behaviour: EofBehaviour,
+    /// Asked on every loop iteration whether to go on, if the program is interruptible.
+    should_continue: Option<FuncRef>,
+    abort_block: Option<Block>,
 }
 
 impl<'a, 'b> Emitter<'a, 'b> {
@@ -220,39 +1416,187 @@ impl<'a, 'b> Emitter<'a, 'b> {
         match instruction {
             Instruction::Debug => {}
             Instruction::Increment => {
-                let val = self
-                    .builder
-                    .ins()
-                    .load(types::I8, MemFlags::new(), self.array_ptr, 0);
+                let val = self.load_cell(self.array_ptr);
                 let new_val = self.builder.ins().iadd_imm(val, 1);
 
-                self.builder
-                    .ins()
-                    .store(MemFlags::new(), new_val, self.array_ptr, 0);
+                self.store_cell(new_val, self.array_ptr);
             }
             Instruction::Decrement => {
-                let val = self
-                    .builder
-                    .ins()
-                    .load(types::I8, MemFlags::new(), self.array_ptr, 0);
+                let val = self.load_cell(self.array_ptr);
                 let new_val = self.builder.ins().iadd_imm(val, -1);
 
-                self.builder
-                    .ins()
-                    .store(MemFlags::new(), new_val, self.array_ptr, 0);
+                self.store_cell(new_val, self.array_ptr);
+            }
+            Instruction::Add(n) => {
+                let val = self.load_cell(self.array_ptr);
+                let new_val = self.builder.ins().iadd_imm(val, *n as i64);
+
+                self.store_cell(new_val, self.array_ptr);
             }
             Instruction::Right => {
-                self.array_ptr = self.builder.ins().iadd_imm(self.array_ptr, 1);
+                self.move_pointer(1);
             }
             Instruction::Left => {
-                self.array_ptr = self.builder.ins().iadd_imm(self.array_ptr, -1);
+                self.move_pointer(-1);
+            }
+            Instruction::Move(offset) => {
+                self.move_pointer(*offset as i64);
+            }
+            Instruction::SetZero => {
+                let zero = self.builder.ins().iconst(self.cell_type, 0);
+                self.store_cell(zero, self.array_ptr);
+            }
+            Instruction::Set(value) => {
+                let value = self.builder.ins().iconst(self.cell_type, *value as i64);
+                self.store_cell(value, self.array_ptr);
+            }
+            Instruction::MultiplyAdd { targets } => {
+                // the pointer doesn't move, so its value before the check stays valid
+                let array_ptr = self.array_ptr;
+
+                let body_block = self.builder.create_block();
+                let next_block = self.builder.create_block();
+
+                let val = self.load_cell(array_ptr);
+                self.builder
+                    .ins()
+                    .brif(val, body_block, &[], next_block, &[]);
+
+                self.builder.seal_block(body_block);
+                self.builder.switch_to_block(body_block);
+
+                for &(offset, factor) in targets {
+                    let target_ptr = self.cell_address(offset as i64);
+                    let target = self.load_cell(target_ptr);
+                    let product = self.builder.ins().imul_imm(val, factor as i64);
+                    let sum = self.builder.ins().iadd(target, product);
+                    self.store_cell(sum, target_ptr);
+                }
+                let zero = self.builder.ins().iconst(self.cell_type, 0);
+                self.store_cell(zero, array_ptr);
+                self.builder.ins().jump(next_block, &[]);
+
+                self.builder.seal_block(next_block);
+                self.builder.switch_to_block(next_block);
+                self.array_ptr = array_ptr;
             }
             Instruction::Output => {
+                let cell = self.load_cell(self.array_ptr);
+                let val = if self.cell_type == types::I32 {
+                    cell
+                } else {
+                    self.builder.ins().uextend(types::I32, cell)
+                };
+                match self.wide_output {
+                    // putchar only writes the lowest byte of the value
+                    _ if self.cell_type == types::I8 => self.putchar(val),
+                    WideOutput::LowByte => self.putchar(val),
+                    WideOutput::Error => {
+                        let too_large =
+                            self.builder
+                                .ins()
+                                .icmp_imm(IntCC::UnsignedGreaterThan, val, 0xff);
+                        self.builder
+                            .ins()
+                            .trapnz(too_large, TrapCode::INTEGER_OVERFLOW);
+                        self.putchar(val);
+                    }
+                    WideOutput::Unicode => self.put_utf8(val),
+                }
+            }
+            Instruction::InitCells {
+                base_offset,
+                values,
+            } => {
+                let cell_bytes = self.cell_type.bytes() as usize;
+                let big_endian =
+                    (self.module.isa().endianness() == Endianness::Big) != self.swap_cell_bytes;
+                let cells: Vec<u8> = values
+                    .iter()
+                    .flat_map(|&value| {
+                        let value = u32::from(value);
+                        if big_endian {
+                            value.to_be_bytes()[4 - cell_bytes..].to_vec()
+                        } else {
+                            value.to_le_bytes()[..cell_bytes].to_vec()
+                        }
+                    })
+                    .collect();
+
+                let data_id = self.module.declare_anonymous_data(false, false).unwrap();
+                let mut data = DataDescription::new();
+                let len = cells.len();
+                data.define(cells.into_boxed_slice());
+                self.module.define_data(data_id, &data).unwrap();
+
+                let ptr_t = self.module.target_config().pointer_type();
+                let gv = self.module.declare_data_in_func(data_id, self.builder.func);
+                let data_ptr = self.builder.ins().global_value(ptr_t, gv);
+
+                let dest = self
+                    .builder
+                    .ins()
+                    .iadd_imm(self.array_ptr, (base_offset * cell_bytes) as i64);
+                // the first cell is at the pointer, so the last one is the only one to check
+                let last_cell = self.builder.ins().iadd_imm(dest, (len - cell_bytes) as i64);
+                self.check_in_bounds(last_cell);
+                let len = self.builder.ins().iconst(ptr_t, len as i64);
+                self.builder
+                    .call_memcpy(self.module.target_config(), dest, data_ptr, len);
+            }
+            Instruction::OutputBytes(bytes) => {
+                let data_id = self.module.declare_anonymous_data(false, false).unwrap();
+                let mut data = DataDescription::new();
+                data.define(bytes.clone().into_boxed_slice());
+                self.module.define_data(data_id, &data).unwrap();
+
+                let ptr_t = self.module.target_config().pointer_type();
+                let gv = self.module.declare_data_in_func(data_id, self.builder.func);
+                let data_ptr = self.builder.ins().global_value(ptr_t, gv);
+
+                // the loop doesn't move the pointer, so its value before the loop stays valid
+                let array_ptr = self.array_ptr;
+
+                let loop_test_block = self.builder.create_block();
+                self.builder.append_block_param(loop_test_block, size_t);
+                let zero = self.builder.ins().iconst(size_t, 0);
+                self.builder
+                    .ins()
+                    .jump(loop_test_block, &[BlockArg::Value(zero)]);
+                self.builder.switch_to_block(loop_test_block);
+
+                let body_block = self.builder.create_block();
+                let exit_block = self.builder.create_block();
+
+                let index = self
+                    .builder
+                    .block_params(self.builder.current_block().unwrap())[0];
+                let in_bounds =
+                    self.builder
+                        .ins()
+                        .icmp_imm(IntCC::UnsignedLessThan, index, bytes.len() as i64);
+                self.builder
+                    .ins()
+                    .brif(in_bounds, body_block, &[], exit_block, &[]);
+
+                self.builder.seal_block(body_block);
+                self.builder.seal_block(exit_block);
+                self.builder.switch_to_block(body_block);
+
+                let byte_ptr = self.builder.ins().iadd(data_ptr, index);
                 let val = self
                     .builder
                     .ins()
-                    .sload8(types::I32, MemFlags::new(), self.array_ptr, 0);
-                self.builder.ins().call(self.putchar, &[val]);
+                    .uload8(types::I32, MemFlags::new(), byte_ptr, 0);
+                self.putchar(val);
+                let next_index = self.builder.ins().iadd_imm(index, 1);
+                self.builder
+                    .ins()
+                    .jump(loop_test_block, &[BlockArg::Value(next_index)]);
+
+                self.builder.seal_block(loop_test_block);
+                self.builder.switch_to_block(exit_block);
+                self.array_ptr = array_ptr;
             }
             Instruction::Input => {
                 let inst = self.builder.ins().call(self.getchar, &[]);
@@ -296,6 +1640,14 @@ impl<'a, 'b> Emitter<'a, 'b> {
                             &[BlockArg::Value(self.array_ptr), BlockArg::Value(zero)],
                         );
                     }
+                    EofBehaviour::Negative => {
+                        // the store truncates -1 to all ones at the width of a cell
+                        let negative = self.builder.ins().iconst(types::I32, -1);
+                        self.builder.ins().jump(
+                            store_block,
+                            &[BlockArg::Value(self.array_ptr), BlockArg::Value(negative)],
+                        );
+                    }
                 }
 
                 self.builder.seal_block(store_block);
@@ -307,9 +1659,12 @@ impl<'a, 'b> Emitter<'a, 'b> {
                 self.array_ptr = block_params[0];
                 let val = block_params[1];
 
-                self.builder
-                    .ins()
-                    .istore8(MemFlags::new(), val, self.array_ptr, 0);
+                let val = if self.cell_type == types::I32 {
+                    val
+                } else {
+                    self.builder.ins().ireduce(self.cell_type, val)
+                };
+                self.store_cell(val, self.array_ptr);
                 self.builder
                     .ins()
                     .jump(next_block, &[BlockArg::Value(self.array_ptr)]);
@@ -336,10 +1691,7 @@ impl<'a, 'b> Emitter<'a, 'b> {
                 self.array_ptr = self
                     .builder
                     .block_params(self.builder.current_block().unwrap())[0];
-                let val = self
-                    .builder
-                    .ins()
-                    .load(types::I8, MemFlags::new(), self.array_ptr, 0);
+                let val = self.load_cell(self.array_ptr);
                 self.builder.ins().brif(
                     val,
                     then_block,
@@ -358,10 +1710,7 @@ impl<'a, 'b> Emitter<'a, 'b> {
                 for i in instructions {
                     self.emit(i);
                 }
-
-                self.builder
-                    .ins()
-                    .jump(loop_test_block, &[BlockArg::Value(self.array_ptr)]);
+                self.jump_back(loop_test_block);
 
                 self.builder.seal_block(loop_test_block);
                 self.builder.switch_to_block(else_block);
@@ -369,6 +1718,220 @@ impl<'a, 'b> Emitter<'a, 'b> {
                     .builder
                     .block_params(self.builder.current_block().unwrap())[0];
             }
+            Instruction::Scan { step } => {
+                // a loop around a single move, without the blocks for a body
+                let scan_block = self.builder.create_block();
+                self.builder.append_block_param(scan_block, size_t);
+                let step_block = self.builder.create_block();
+                let exit_block = self.builder.create_block();
+                self.builder.append_block_param(exit_block, size_t);
+
+                self.builder
+                    .ins()
+                    .jump(scan_block, &[BlockArg::Value(self.array_ptr)]);
+                self.builder.switch_to_block(scan_block);
+                self.array_ptr = self.builder.block_params(scan_block)[0];
+                let val = self.load_cell(self.array_ptr);
+                self.builder.ins().brif(
+                    val,
+                    step_block,
+                    &[],
+                    exit_block,
+                    &[BlockArg::Value(self.array_ptr)],
+                );
+
+                self.builder.seal_block(step_block);
+                self.builder.seal_block(exit_block);
+                self.builder.switch_to_block(step_block);
+                self.move_pointer(*step as i64);
+                self.jump_back(scan_block);
+
+                self.builder.seal_block(scan_block);
+                self.builder.switch_to_block(exit_block);
+                self.array_ptr = self.builder.block_params(exit_block)[0];
+            }
+        }
+    }
+
+    /// Ends an iteration of a loop by jumping back to its test in `loop_test_block`, unless the
+    /// program is interruptible and `should_continue` returns 0.
+    fn jump_back(&mut self, loop_test_block: Block) {
+        if let Some(should_continue) = self.should_continue {
+            let call = self.builder.ins().call(should_continue, &[]);
+            let result = self.builder.inst_results(call)[0];
+            let stop = self.builder.ins().icmp_imm(IntCC::Equal, result, 0);
+            self.builder.ins().brif(
+                stop,
+                self.abort_block.expect("interruptible programs can abort"),
+                &[],
+                loop_test_block,
+                &[BlockArg::Value(self.array_ptr)],
+            );
+        } else {
+            self.builder
+                .ins()
+                .jump(loop_test_block, &[BlockArg::Value(self.array_ptr)]);
+        }
+    }
+
+    /// Moves the pointer by `offset` cells.
+    fn move_pointer(&mut self, offset: i64) {
+        self.array_ptr = self.cell_address(offset);
+    }
+
+    /// Returns the address of the cell `offset` cells from the pointer.
+    fn cell_address(&mut self, offset: i64) -> Value {
+        let bytes = offset * self.cell_type.bytes() as i64;
+        let Some(tape_start) = self.tape_start else {
+            return self.builder.ins().iadd_imm(self.array_ptr, bytes);
+        };
+        if self.check_bounds {
+            let address = self.builder.ins().iadd_imm(self.array_ptr, bytes);
+            self.check_in_bounds(address);
+            return address;
+        }
+
+        // move rightwards by the equivalent offset within the tape, then wrap past the end
+        let bytes = bytes.rem_euclid(self.tape_bytes);
+        if bytes == 0 {
+            return self.array_ptr;
+        }
+        let moved = self.builder.ins().iadd_imm(self.array_ptr, bytes);
+        let tape_end = self.builder.ins().iadd_imm(tape_start, self.tape_bytes);
+        let past_end = self
+            .builder
+            .ins()
+            .icmp(IntCC::UnsignedGreaterThanOrEqual, moved, tape_end);
+        let wrapped = self.builder.ins().iadd_imm(moved, -self.tape_bytes);
+        self.builder.ins().select(past_end, wrapped, moved)
+    }
+
+    /// Traps unless `address` lies within the tape, if bounds are checked.
+    fn check_in_bounds(&mut self, address: Value) {
+        let Some(tape_start) = self.tape_start.filter(|_| self.check_bounds) else {
+            return;
+        };
+        // addresses before the start wrap around to huge offsets, so one comparison covers both
+        // ends
+        let offset = self.builder.ins().isub(address, tape_start);
+        let outside =
+            self.builder
+                .ins()
+                .icmp_imm(IntCC::UnsignedGreaterThanOrEqual, offset, self.tape_bytes);
+        self.builder
+            .ins()
+            .trapnz(outside, TrapCode::HEAP_OUT_OF_BOUNDS);
+    }
+
+    fn load_cell(&mut self, ptr: Value) -> Value {
+        let val = self
+            .builder
+            .ins()
+            .load(self.cell_type, MemFlags::new(), ptr, 0);
+        if self.swap_cell_bytes {
+            self.builder.ins().bswap(val)
+        } else {
+            val
+        }
+    }
+
+    fn store_cell(&mut self, val: Value, ptr: Value) {
+        let val = if self.swap_cell_bytes {
+            self.builder.ins().bswap(val)
+        } else {
+            val
+        };
+        self.builder.ins().store(MemFlags::new(), val, ptr, 0);
+    }
+
+    /// Writes the character `val` encoded as UTF-8, trapping if it isn't a Unicode scalar value.
+    fn put_utf8(&mut self, val: Value) {
+        let size_t = Type::int(self.module.target_config().pointer_bits() as u16).unwrap();
+
+        let too_large = self
+            .builder
+            .ins()
+            .icmp_imm(IntCC::UnsignedGreaterThan, val, 0x10ffff);
+        let plane = self.builder.ins().band_imm(val, !0x7ff);
+        let surrogate = self.builder.ins().icmp_imm(IntCC::Equal, plane, 0xd800);
+        let invalid = self.builder.ins().bor(too_large, surrogate);
+        self.builder
+            .ins()
+            .trapnz(invalid, TrapCode::INTEGER_OVERFLOW);
+
+        // each threshold the character reaches adds a continuation byte and changes the first
+        // byte, which holds the bits left over
+        let thresholds = [(0x80, 0xc0, 6), (0x800, 0xe0, 12), (0x10000, 0xf0, 18)];
+        let mut first = val;
+        for (threshold, marker, shift) in thresholds {
+            let reached =
+                self.builder
+                    .ins()
+                    .icmp_imm(IntCC::UnsignedGreaterThanOrEqual, val, threshold);
+            let bits = self.builder.ins().ushr_imm(val, shift);
+            let marked = self.builder.ins().bor_imm(bits, marker);
+            first = self.builder.ins().select(reached, marked, first);
+        }
+        self.putchar(first);
+
+        for (threshold, _, shift) in thresholds.into_iter().rev() {
+            let reached =
+                self.builder
+                    .ins()
+                    .icmp_imm(IntCC::UnsignedGreaterThanOrEqual, val, threshold);
+            let put_block = self.builder.create_block();
+            self.builder.append_block_param(put_block, size_t);
+            let next_block = self.builder.create_block();
+            self.builder.append_block_param(next_block, size_t);
+            self.builder.ins().brif(
+                reached,
+                put_block,
+                &[BlockArg::Value(self.array_ptr)],
+                next_block,
+                &[BlockArg::Value(self.array_ptr)],
+            );
+
+            self.builder.seal_block(put_block);
+            self.builder.switch_to_block(put_block);
+            self.array_ptr = self.builder.block_params(put_block)[0];
+            let bits = self.builder.ins().ushr_imm(val, shift - 6);
+            let bits = self.builder.ins().band_imm(bits, 0x3f);
+            let continuation = self.builder.ins().bor_imm(bits, 0x80);
+            self.putchar(continuation);
+            self.builder
+                .ins()
+                .jump(next_block, &[BlockArg::Value(self.array_ptr)]);
+
+            self.builder.seal_block(next_block);
+            self.builder.switch_to_block(next_block);
+            self.array_ptr = self.builder.block_params(next_block)[0];
+        }
+    }
+
+    fn putchar(&mut self, val: Value) {
+        let size_t = Type::int(self.module.target_config().pointer_bits() as u16).unwrap();
+
+        let call = self.builder.ins().call(self.putchar, &[val]);
+
+        if let Some(abort_block) = self.abort_block {
+            let result = self.builder.inst_results(call)[0];
+            let failed = self.builder.ins().icmp_imm(IntCC::Equal, result, -1);
+
+            let next_block = self.builder.create_block();
+            self.builder.append_block_param(next_block, size_t);
+            self.builder.ins().brif(
+                failed,
+                abort_block,
+                &[],
+                next_block,
+                &[BlockArg::Value(self.array_ptr)],
+            );
+
+            self.builder.seal_block(next_block);
+            self.builder.switch_to_block(next_block);
+            self.array_ptr = self
+                .builder
+                .block_params(self.builder.current_block().unwrap())[0];
         }
     }
 }
@@ -0,0 +1,286 @@
+use std::{env, ffi::OsString, fs, io, path::PathBuf};
+
+use clap::ValueEnum;
+
+use crate::{
+    cli::{Args, CellSize, DEFAULT_ARRAY_SIZE, EofBehaviour},
+    compiler,
+};
+
+pub const CONFIG_FILE_NAME: &str = "brainlift.toml";
+
+/// Persistent defaults read from a `brainlift.toml` file.
+///
+/// Only a flat subset of TOML is understood: one `key = value` pair per line, with integers,
+/// booleans, quoted strings and `#` comments.
+#[derive(Debug, Default)]
+pub struct Config {
+    pub array_size: Option<u32>,
+    pub eof_behaviour: Option<EofBehaviour>,
+    /// The `-O` level.
+    pub optimize: Option<u8>,
+    pub cell_size: Option<CellSize>,
+    pub target: Option<String>,
+    pub pointer_wrap: Option<bool>,
+    pub signed_tape: Option<bool>,
+    pub fixed_tape: Option<bool>,
+    /// `Some(None)` if the number of steps is explicitly unlimited, written as `"none"`.
+    pub max_steps: Option<Option<u64>>,
+}
+
+impl Config {
+    /// Loads `brainlift.toml` from the current directory or, if there is none, from
+    /// `$XDG_CONFIG_HOME/brainlift/` (falling back to `~/.config/brainlift/`).
+    /// A malformed file is reported as a warning and the built-in defaults are used instead.
+    pub fn load() -> Self {
+        let Some(path) = Self::find() else {
+            return Self::default();
+        };
+
+        let config = fs::read_to_string(&path)
+            .map_err(ConfigError::Io)
+            .and_then(|content| Self::parse(&content));
+
+        match config {
+            Ok(config) => config,
+            Err(e) => {
+                eprintln!("warning: ignoring config file {path:?}: {e}");
+                Self::default()
+            }
+        }
+    }
+
+    fn find() -> Option<PathBuf> {
+        let local = PathBuf::from(CONFIG_FILE_NAME);
+        if local.is_file() {
+            return Some(local);
+        }
+
+        let config_dir = env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+
+        let global = config_dir.join("brainlift").join(CONFIG_FILE_NAME);
+        global.is_file().then_some(global)
+    }
+
+    /// Parses the content of a `brainlift.toml` file.
+    ///
+    /// ```
+    /// use brainlift::config::Config;
+    ///
+    /// let config = Config::parse("optimize = 2\ncell_size = \"16\" # wide cells\n").unwrap();
+    /// assert_eq!(config.to_args(), ["--optimize=2", "--cell-size=16"]);
+    /// assert!(Config::parse("optimize = 3").is_err());
+    /// assert!(Config::parse("cell_size = 12").is_err());
+    /// ```
+    pub fn parse(content: &str) -> Result<Self, ConfigError> {
+        let mut config = Self::default();
+
+        for (index, line) in content.lines().enumerate() {
+            let line_number = index + 1;
+
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(ConfigError::Syntax(line_number));
+            };
+            let key = key.trim();
+            let value = Self::value(value.trim()).ok_or(ConfigError::Syntax(line_number))?;
+
+            let invalid = || ConfigError::InvalidValue(key.to_string(), line_number);
+            match key {
+                "array_size" => {
+                    let size = value.parse::<u32>().map_err(|_| invalid())?;
+                    if size == 0 {
+                        return Err(invalid());
+                    }
+                    config.array_size = Some(size);
+                }
+                "eof_behaviour" => {
+                    let eof = EofBehaviour::from_str(value, true).map_err(|_| invalid())?;
+                    config.eof_behaviour = Some(eof);
+                }
+                "optimize" => {
+                    let level = value.parse::<u8>().map_err(|_| invalid())?;
+                    if level > 2 {
+                        return Err(invalid());
+                    }
+                    config.optimize = Some(level);
+                }
+                "cell_size" => {
+                    let size = CellSize::from_str(value, true).map_err(|_| invalid())?;
+                    config.cell_size = Some(size);
+                }
+                "target" => {
+                    if !compiler::supported_targets().contains(&value) {
+                        return Err(invalid());
+                    }
+                    config.target = Some(value.to_string());
+                }
+                "pointer_wrap" => config.pointer_wrap = Some(value.parse().map_err(|_| invalid())?),
+                "signed_tape" => config.signed_tape = Some(value.parse().map_err(|_| invalid())?),
+                "fixed_tape" => config.fixed_tape = Some(value.parse().map_err(|_| invalid())?),
+                "max_steps" => {
+                    let steps = match value {
+                        "none" => None,
+                        steps => Some(steps.parse::<u64>().map_err(|_| invalid())?),
+                    };
+                    config.max_steps = Some(steps);
+                }
+                _ => return Err(ConfigError::UnknownKey(key.to_string(), line_number)),
+            }
+        }
+
+        Ok(config)
+    }
+
+    /// Strips quotes and trailing comments from a raw value.
+    fn value(raw: &str) -> Option<&str> {
+        if let Some(quoted) = raw.strip_prefix('"') {
+            let (value, rest) = quoted.split_once('"')?;
+            let rest = rest.trim();
+            (rest.is_empty() || rest.starts_with('#')).then_some(value)
+        } else {
+            let value = raw.split('#').next().unwrap_or_default().trim();
+            (!value.is_empty()).then_some(value)
+        }
+    }
+
+    /// Returns the effective settings of `args` as a fully populated config. Note that a
+    /// program's `;tape` directive can still override the default array size.
+    ///
+    /// ```
+    /// use brainlift::{cli::Args, config::Config};
+    /// use clap::Parser;
+    ///
+    /// let args = Args::parse_from(["brainlift", "-O1", "--signed-tape", "--max-steps", "500"]);
+    /// let printed = Config::resolved(&args).to_string();
+    /// assert!(printed.contains("optimize = 1\n"));
+    /// assert!(printed.contains("signed_tape = true\n"));
+    /// assert!(printed.contains("max_steps = 500\n"));
+    ///
+    /// // the printed config can be used as a config file with the same effect
+    /// let reloaded = Config::parse(&printed).unwrap();
+    /// let args = Args::parse_from(std::iter::once("brainlift".into()).chain(reloaded.to_args()));
+    /// assert_eq!(Config::resolved(&args).to_string(), printed);
+    /// ```
+    pub fn resolved(args: &Args) -> Self {
+        Self {
+            array_size: Some(args.array_size.unwrap_or(DEFAULT_ARRAY_SIZE)),
+            eof_behaviour: Some(args.eof_behaviour),
+            optimize: Some(args.optimize),
+            cell_size: Some(args.cell_size),
+            target: Some(args.target.clone()),
+            pointer_wrap: Some(args.pointer_wrap),
+            signed_tape: Some(args.signed_tape),
+            fixed_tape: Some(args.fixed_tape),
+            max_steps: Some(args.max_steps),
+        }
+    }
+
+    /// Returns the config as command line options, to be passed as defaults to
+    /// [`Args::parse_over_defaults`].
+    pub fn to_args(&self) -> Vec<OsString> {
+        let mut args = Vec::new();
+
+        if let Some(array_size) = self.array_size {
+            args.push(format!("--array-size={array_size}").into());
+        }
+        if let Some(eof) = self.eof_behaviour {
+            args.push(format!("--eof-behaviour={}", Self::value_name(eof)).into());
+        }
+        if let Some(level) = self.optimize {
+            args.push(format!("--optimize={level}").into());
+        }
+        if let Some(cell_size) = self.cell_size {
+            args.push(format!("--cell-size={}", Self::value_name(cell_size)).into());
+        }
+        if let Some(target) = &self.target {
+            args.push(format!("--target={target}").into());
+        }
+        // the flags are off by default, so only the ones turned on need to be passed
+        if self.pointer_wrap == Some(true) {
+            args.push("--pointer-wrap".into());
+        }
+        if self.signed_tape == Some(true) {
+            args.push("--signed-tape".into());
+        }
+        if self.fixed_tape == Some(true) {
+            args.push("--fixed-tape".into());
+        }
+        if let Some(Some(steps)) = self.max_steps {
+            args.push(format!("--max-steps={steps}").into());
+        }
+
+        args
+    }
+
+    fn value_name(value: impl ValueEnum) -> String {
+        let value = value.to_possible_value().expect("no variant is skipped");
+        value.get_name().to_string()
+    }
+}
+
+/// Formats the config in the same syntax as `brainlift.toml`.
+impl std::fmt::Display for Config {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(array_size) = self.array_size {
+            writeln!(f, "array_size = {array_size}")?;
+        }
+        if let Some(eof) = self.eof_behaviour {
+            writeln!(f, "eof_behaviour = \"{}\"", Self::value_name(eof))?;
+        }
+        if let Some(level) = self.optimize {
+            writeln!(f, "optimize = {level}")?;
+        }
+        if let Some(cell_size) = self.cell_size {
+            writeln!(f, "cell_size = {}", Self::value_name(cell_size))?;
+        }
+        if let Some(target) = &self.target {
+            writeln!(f, "target = \"{target}\"")?;
+        }
+        if let Some(pointer_wrap) = self.pointer_wrap {
+            writeln!(f, "pointer_wrap = {pointer_wrap}")?;
+        }
+        if let Some(signed_tape) = self.signed_tape {
+            writeln!(f, "signed_tape = {signed_tape}")?;
+        }
+        if let Some(fixed_tape) = self.fixed_tape {
+            writeln!(f, "fixed_tape = {fixed_tape}")?;
+        }
+        match self.max_steps {
+            Some(Some(steps)) => writeln!(f, "max_steps = {steps}")?,
+            Some(None) => writeln!(f, "max_steps = \"none\"")?,
+            None => {}
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(io::Error),
+    Syntax(usize),
+    UnknownKey(String, usize),
+    InvalidValue(String, usize),
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::Io(e) => write!(f, "{e}"),
+            ConfigError::Syntax(line) => write!(f, "expected `key = value` in line {line}"),
+            ConfigError::UnknownKey(key, line) => write!(f, "unknown key `{key}` in line {line}"),
+            ConfigError::InvalidValue(key, line) => {
+                write!(f, "invalid value for `{key}` in line {line}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
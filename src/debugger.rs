@@ -0,0 +1,98 @@
+use std::io::{self, Write};
+
+use crate::{
+    interpreter::{Cell, DEFAULT_DEBUG_WINDOW, Interpreter, RunStatus, RuntimeError},
+    program::FlatProgram,
+};
+
+const HELP: &str = "\
+commands:
+  s, step [N]        execute the next N instructions, 1 by default, as does an empty line
+  c, continue        run until the next `#`
+  t, tape [RADIUS]   show the cells around the pointer
+  p, pointer         show the pointer position
+  q, quit            stop the program
+  h, help            show this help";
+
+/// Runs `program`, stopping at every `#` to read commands with `read_command` until the user
+/// continues. It appends one line to the buffer like [`io::BufRead::read_line`], and is only
+/// called while the program is stopped, so it may read from the same stream as `,` does. The
+/// prompt and all responses are written to `terminal`, so that they don't mix with the
+/// program's output. The run continues without stopping once there are no more commands.
+pub fn debug<C: Cell>(
+    interpreter: &mut Interpreter<'_, C>,
+    program: &FlatProgram,
+    mut read_command: impl FnMut(&mut String) -> io::Result<usize>,
+    mut terminal: impl Write,
+) -> Result<(), RuntimeError> {
+    while interpreter.run_to_breakpoint(program)? == RunStatus::Yielded {
+        writeln!(terminal, "stopped at `#`")?;
+        show_position(interpreter, program, &mut terminal)?;
+
+        loop {
+            write!(terminal, "(debug) ")?;
+            terminal.flush()?;
+
+            let mut line = String::new();
+            if read_command(&mut line)? == 0 {
+                writeln!(terminal)?;
+                return run_to_end(interpreter, program);
+            }
+            let mut words = line.split_whitespace();
+            let command = words.next().unwrap_or("step");
+            let number = words.next().map(str::parse::<usize>).transpose();
+
+            match (command, number) {
+                ("s" | "step", Ok(count)) => {
+                    for _ in 0..count.unwrap_or(1) {
+                        if interpreter.step(program)? == RunStatus::Finished {
+                            writeln!(terminal, "program finished")?;
+                            return Ok(());
+                        }
+                    }
+                    show_position(interpreter, program, &mut terminal)?;
+                }
+                ("c" | "continue", Ok(None)) => break,
+                ("t" | "tape", Ok(radius)) => {
+                    let radius = radius.unwrap_or(DEFAULT_DEBUG_WINDOW);
+                    writeln!(terminal, "{}", interpreter.tape_window(radius))?;
+                }
+                ("p" | "pointer", Ok(None)) => {
+                    writeln!(terminal, "pointer: {}", interpreter.position())?
+                }
+                ("q" | "quit", Ok(None)) => return Ok(()),
+                ("h" | "help", Ok(None)) => writeln!(terminal, "{HELP}")?,
+                _ => writeln!(terminal, "unknown command `{}`, try `help`", line.trim())?,
+            }
+        }
+    }
+
+    writeln!(terminal, "program finished")?;
+    Ok(())
+}
+
+/// Lets the program run to its end, skipping all further `#`.
+fn run_to_end<C: Cell>(
+    interpreter: &mut Interpreter<'_, C>,
+    program: &FlatProgram,
+) -> Result<(), RuntimeError> {
+    while interpreter.run_to_breakpoint(program)? == RunStatus::Yielded {}
+    Ok(())
+}
+
+/// Shows the next instruction and where it is in the source.
+fn show_position<C: Cell>(
+    interpreter: &Interpreter<'_, C>,
+    program: &FlatProgram,
+    terminal: &mut impl Write,
+) -> io::Result<()> {
+    let index = interpreter.next_instruction();
+    let Some(instruction) = program.instructions.get(index) else {
+        return writeln!(terminal, "at the end of the program");
+    };
+    match program.span(index) {
+        Some(span) => write!(terminal, "next: {instruction:?} at {span}")?,
+        None => write!(terminal, "next: {instruction:?}")?,
+    }
+    writeln!(terminal, ", pointer: {}", interpreter.position())
+}
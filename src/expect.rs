@@ -0,0 +1,130 @@
+use std::{
+    collections::VecDeque,
+    io::{self, BufRead, Bytes, Write},
+};
+
+/// Number of output bytes shown before a mismatch.
+const CONTEXT_LEN: usize = 32;
+
+/// A sink that compares everything written to it against an expected output as it arrives, so
+/// that neither has to be held in memory as a whole.
+///
+/// `check --quine` compares the output of a program run without input to its source this way:
+///
+/// ```standalone_crate
+/// use brainlift::{EofBehaviour, Interpreter, Program, expect::ExpectedOutput, parser};
+///
+/// let check_quine = |source: &str| {
+///     let expected = parser::minify(source);
+///     let mut output = ExpectedOutput::new(&expected[..]);
+///     Interpreter::new(30_000, EofBehaviour::Ignore)
+///         .with_io(std::io::empty(), &mut output)
+///         .run(&Program::parse(source).unwrap())
+///         .unwrap();
+///     output.finish().unwrap()
+/// };
+///
+/// assert!(check_quine(include_str!("../examples/quine.b")).is_none());
+///
+/// // prints "c" instead of its source
+/// let mismatch = check_quine("+++++++++[>+++++++++++<-]>.").unwrap();
+/// assert_eq!(
+///     mismatch.to_string(),
+///     "output differs at byte 0: expected 0x2b '+', got 0x63 'c'\nafter: \"\""
+/// );
+/// ```
+pub struct ExpectedOutput<R: BufRead> {
+    expected: Bytes<R>,
+    offset: usize,
+    context: VecDeque<u8>,
+    mismatch: Option<Mismatch>,
+}
+
+/// The first difference between the actual and the expected output. `None` means the
+/// respective output ended at `offset`.
+#[derive(Debug)]
+pub struct Mismatch {
+    pub offset: usize,
+    pub expected: Option<u8>,
+    pub actual: Option<u8>,
+    /// The output leading up to the mismatch.
+    pub context: Vec<u8>,
+}
+
+impl<R: BufRead> ExpectedOutput<R> {
+    pub fn new(expected: R) -> Self {
+        Self {
+            expected: expected.bytes(),
+            offset: 0,
+            context: VecDeque::with_capacity(CONTEXT_LEN),
+            mismatch: None,
+        }
+    }
+
+    /// Checks that the expected output ended as well and returns the first mismatch, if any.
+    pub fn finish(mut self) -> io::Result<Option<Mismatch>> {
+        if self.mismatch.is_none()
+            && let Some(expected) = self.expected.next().transpose()?
+        {
+            self.mismatch = Some(self.mismatch_at(Some(expected), None));
+        }
+
+        Ok(self.mismatch)
+    }
+
+    fn mismatch_at(&self, expected: Option<u8>, actual: Option<u8>) -> Mismatch {
+        Mismatch {
+            offset: self.offset,
+            expected,
+            actual,
+            context: self.context.iter().copied().collect(),
+        }
+    }
+}
+
+impl<R: BufRead> Write for ExpectedOutput<R> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // once the outputs have diverged, the rest is irrelevant
+        if self.mismatch.is_some() {
+            return Ok(buf.len());
+        }
+
+        for &actual in buf {
+            let expected = self.expected.next().transpose()?;
+            if expected != Some(actual) {
+                self.mismatch = Some(self.mismatch_at(expected, Some(actual)));
+                break;
+            }
+
+            if self.context.len() == CONTEXT_LEN {
+                self.context.pop_front();
+            }
+            self.context.push_back(actual);
+            self.offset += 1;
+        }
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let describe = |byte: Option<u8>| match byte {
+            Some(byte) => format!("{byte:#04x} {:?}", byte as char),
+            None => "end of output".to_string(),
+        };
+
+        writeln!(
+            f,
+            "output differs at byte {}: expected {}, got {}",
+            self.offset,
+            describe(self.expected),
+            describe(self.actual)
+        )?;
+        write!(f, "after: \"{}\"", self.context.escape_ascii())
+    }
+}
@@ -0,0 +1,93 @@
+use std::io::{self, Write};
+
+use crate::interpreter::FinishOutput;
+
+const BYTES_PER_LINE: usize = 16;
+
+/// Writes everything written to it as a hex dump in the format of `xxd`, one line per 16 bytes
+/// with the offset, the bytes in hex and their printable ASCII characters.
+///
+/// A trailing partial line is only written by [`HexDump::finish`], so that flushing doesn't
+/// break up lines. Dropping the dump finishes it too, but can't report errors.
+pub struct HexDump<W: Write> {
+    inner: W,
+    offset: usize,
+    line: Vec<u8>,
+}
+
+impl<W: Write> HexDump<W> {
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            offset: 0,
+            line: Vec::with_capacity(BYTES_PER_LINE),
+        }
+    }
+
+    /// Writes the trailing partial line, if there is one, and flushes.
+    pub fn finish(&mut self) -> io::Result<()> {
+        if !self.line.is_empty() {
+            self.write_line()?;
+        }
+        self.inner.flush()
+    }
+
+    fn write_line(&mut self) -> io::Result<()> {
+        let mut hex = String::new();
+        for (i, byte) in self.line.iter().enumerate() {
+            if i % 2 == 0 && i > 0 {
+                hex.push(' ');
+            }
+            hex.push_str(&format!("{byte:02x}"));
+        }
+
+        let ascii: String = self
+            .line
+            .iter()
+            .map(|&byte| {
+                if byte.is_ascii_graphic() || byte == b' ' {
+                    byte as char
+                } else {
+                    '.'
+                }
+            })
+            .collect();
+
+        // two hex digits per byte plus a space between each pair of bytes
+        let width = BYTES_PER_LINE * 2 + BYTES_PER_LINE / 2 - 1;
+        writeln!(self.inner, "{:08x}: {hex:<width$}  {ascii}", self.offset)?;
+
+        self.offset += self.line.len();
+        self.line.clear();
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for HexDump<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for &byte in buf {
+            self.line.push(byte);
+            if self.line.len() == BYTES_PER_LINE {
+                self.write_line()?;
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<W: Write> FinishOutput for HexDump<W> {
+    fn finish(&mut self) -> io::Result<()> {
+        HexDump::finish(self)
+    }
+}
+
+impl<W: Write> Drop for HexDump<W> {
+    fn drop(&mut self) {
+        // errors can't be reported from here, like with `BufWriter`
+        let _ = self.finish();
+    }
+}
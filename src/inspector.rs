@@ -0,0 +1,236 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Read, Write},
+    os::fd::AsRawFd,
+};
+
+/// Columns taken up by the offset in front of each row of cells.
+const OFFSET_WIDTH: usize = 8;
+/// Columns taken up by a single cell, including the separating space.
+const CELL_WIDTH: usize = 4;
+
+#[derive(Clone, Copy)]
+enum ValueFormat {
+    Decimal,
+    Hex,
+    Ascii,
+}
+
+impl ValueFormat {
+    fn next(self) -> Self {
+        match self {
+            ValueFormat::Decimal => ValueFormat::Hex,
+            ValueFormat::Hex => ValueFormat::Ascii,
+            ValueFormat::Ascii => ValueFormat::Decimal,
+        }
+    }
+
+    fn format(self, value: u8) -> String {
+        match self {
+            ValueFormat::Decimal => format!("{value:>3}"),
+            ValueFormat::Hex => format!(" {value:02x}"),
+            ValueFormat::Ascii => format!("{:>3}", printable(value)),
+        }
+    }
+}
+
+/// Shows `tape` as a scrollable grid on the terminal until the user quits, with the cell at
+/// `pointer` highlighted. Uses the controlling terminal rather than stdin and stdout, as those
+/// may be redirected for the program that was run.
+pub fn inspect(tape: &[u8], pointer: usize, title: &str) -> io::Result<()> {
+    let mut terminal = RawTerminal::open()?;
+    let mut view = View {
+        tape,
+        pointer,
+        title,
+        selected: pointer,
+        top_row: 0,
+        format: ValueFormat::Decimal,
+    };
+
+    loop {
+        let (columns, rows) = terminal.size()?;
+        view.draw(&mut terminal.tty, columns, rows)?;
+
+        let per_row = cells_per_row(columns);
+        let page = per_row * rows.saturating_sub(3).max(1);
+        let last = tape.len() - 1;
+        match terminal.key()? {
+            Key::Left => view.selected = view.selected.saturating_sub(1),
+            Key::Right => view.selected = (view.selected + 1).min(last),
+            Key::Up => view.selected = view.selected.saturating_sub(per_row),
+            Key::Down => view.selected = (view.selected + per_row).min(last),
+            Key::PageUp => view.selected = view.selected.saturating_sub(page),
+            Key::PageDown => view.selected = (view.selected + page).min(last),
+            Key::Char('p') => view.selected = pointer,
+            Key::Char('m') => view.format = view.format.next(),
+            Key::Char('q') | Key::Escape => return Ok(()),
+            _ => {}
+        }
+    }
+}
+
+struct View<'a> {
+    tape: &'a [u8],
+    pointer: usize,
+    title: &'a str,
+    selected: usize,
+    top_row: usize,
+    format: ValueFormat,
+}
+
+impl View<'_> {
+    fn draw(&mut self, out: &mut impl Write, columns: usize, rows: usize) -> io::Result<()> {
+        let per_row = cells_per_row(columns);
+        let visible_rows = rows.saturating_sub(3).max(1);
+
+        // scroll just far enough to keep the selected cell visible
+        let selected_row = self.selected / per_row;
+        if selected_row < self.top_row {
+            self.top_row = selected_row;
+        } else if selected_row >= self.top_row + visible_rows {
+            self.top_row = selected_row + 1 - visible_rows;
+        }
+
+        let mut screen = String::from("\x1b[H\x1b[2J");
+        screen.push_str(&format!(
+            "\x1b[1m{}\x1b[0m  arrows: move  p: pointer  m: format  q: quit\r\n",
+            self.title
+        ));
+
+        for row in self.top_row..self.top_row + visible_rows {
+            let start = row * per_row;
+            if start >= self.tape.len() {
+                break;
+            }
+
+            screen.push_str(&format!("{start:>0$} ", OFFSET_WIDTH - 1));
+            for index in start..(start + per_row).min(self.tape.len()) {
+                let value = self.format.format(self.tape[index]);
+                let style = match (index == self.pointer, index == self.selected) {
+                    (true, true) => "\x1b[7;4m",
+                    (true, false) => "\x1b[7m",
+                    (false, true) => "\x1b[1;4m",
+                    (false, false) => "",
+                };
+                screen.push_str(&format!(" {style}{value}\x1b[0m"));
+            }
+            screen.push_str("\r\n");
+        }
+
+        let value = self.tape[self.selected];
+        screen.push_str(&format!(
+            "\x1b[{rows};1Hcell {}  dec {value}  hex {value:#04x}  ascii {}  pointer {}",
+            self.selected,
+            printable(value),
+            self.pointer,
+        ));
+
+        out.write_all(screen.as_bytes())?;
+        out.flush()
+    }
+}
+
+fn cells_per_row(columns: usize) -> usize {
+    let fitting = columns.saturating_sub(OFFSET_WIDTH) / CELL_WIDTH;
+    // keep rows aligned to nice offsets where possible
+    match fitting {
+        32.. => 32,
+        16.. => 16,
+        8.. => 8,
+        _ => fitting.max(1),
+    }
+}
+
+fn printable(value: u8) -> String {
+    if value.is_ascii_graphic() {
+        format!("'{}'", value as char)
+    } else {
+        ".".to_string()
+    }
+}
+
+enum Key {
+    Up,
+    Down,
+    Left,
+    Right,
+    PageUp,
+    PageDown,
+    Escape,
+    Char(char),
+    Other,
+}
+
+/// The controlling terminal in raw mode, showing the alternate screen. The previous state is
+/// restored when dropped.
+struct RawTerminal {
+    tty: File,
+    original: libc::termios,
+}
+
+impl RawTerminal {
+    fn open() -> io::Result<Self> {
+        let tty = OpenOptions::new().read(true).write(true).open("/dev/tty")?;
+        let fd = tty.as_raw_fd();
+
+        // SAFETY: `fd` is an open terminal and the termios structs are local
+        let original = unsafe {
+            let mut original = std::mem::zeroed::<libc::termios>();
+            if libc::tcgetattr(fd, &mut original) < 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            let mut raw = original;
+            libc::cfmakeraw(&mut raw);
+            if libc::tcsetattr(fd, libc::TCSAFLUSH, &raw) < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            original
+        };
+
+        let mut terminal = Self { tty, original };
+        // alternate screen, hidden cursor
+        terminal.tty.write_all(b"\x1b[?1049h\x1b[?25l")?;
+        Ok(terminal)
+    }
+
+    fn size(&self) -> io::Result<(usize, usize)> {
+        // SAFETY: `TIOCGWINSZ` only writes to the local winsize struct
+        unsafe {
+            let mut size = std::mem::zeroed::<libc::winsize>();
+            if libc::ioctl(self.tty.as_raw_fd(), libc::TIOCGWINSZ, &mut size) < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok((size.ws_col as usize, size.ws_row as usize))
+        }
+    }
+
+    fn key(&mut self) -> io::Result<Key> {
+        // escape sequences of a single key press arrive together
+        let mut buf = [0; 8];
+        let n = self.tty.read(&mut buf)?;
+
+        Ok(match &buf[..n] {
+            b"\x1b[A" | b"k" => Key::Up,
+            b"\x1b[B" | b"j" => Key::Down,
+            b"\x1b[C" | b"l" => Key::Right,
+            b"\x1b[D" | b"h" => Key::Left,
+            b"\x1b[5~" => Key::PageUp,
+            b"\x1b[6~" => Key::PageDown,
+            b"\x1b" | b"\x03" => Key::Escape,
+            [c] if c.is_ascii() => Key::Char(*c as char),
+            _ => Key::Other,
+        })
+    }
+}
+
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        let _ = self.tty.write_all(b"\x1b[?25h\x1b[?1049l");
+        // SAFETY: restores the attributes read in `open` on the same terminal
+        unsafe {
+            libc::tcsetattr(self.tty.as_raw_fd(), libc::TCSAFLUSH, &self.original);
+        }
+    }
+}
@@ -1,124 +1,1584 @@
 use std::{
     cmp::min,
-    io::{self, Read},
+    collections::{BTreeMap, VecDeque},
+    fmt::{Debug, Display},
+    io::{self, BufRead, BufReader, BufWriter, Read, Write},
+    iter,
+    task::Poll,
+    thread,
 };
 
 use crate::{
-    cli::EofBehaviour,
+    cli::{EofBehaviour, InputMode, TrimOutput, WideOutput},
+    profile::Profile,
     program::{
+        FlatInstruction, FlatProgram,
         Instruction::{self, *},
-        Program,
+        Program, Span,
     },
 };
 
-pub struct Interpreter {
+/// Cells shown on either side of the pointer by `#`, unless set with
+/// [`Interpreter::with_debug_window`].
+pub const DEFAULT_DEBUG_WINDOW: usize = 10;
+
+/// An integer type the tape can be made of. Arithmetic on cells wraps around at the width of
+/// the type.
+pub trait Cell:
+    Copy + Ord + Debug + Display + From<u8> + Into<u32> + TryFrom<u32> + 'static
+{
+    const ZERO: Self;
+    const MAX: Self;
+
+    fn add_signed(self, n: i8) -> Self;
+
+    /// Adds `value` times `factor`.
+    fn multiply_add(self, value: Self, factor: i8) -> Self;
+
+    /// The byte written by `.`.
+    fn low_byte(self) -> u8;
+}
+
+impl Cell for u8 {
+    const ZERO: Self = 0;
+    const MAX: Self = u8::MAX;
+
+    fn add_signed(self, n: i8) -> Self {
+        self.wrapping_add_signed(n)
+    }
+
+    fn multiply_add(self, value: Self, factor: i8) -> Self {
+        self.wrapping_add(value.wrapping_mul(factor as u8))
+    }
+
+    fn low_byte(self) -> u8 {
+        self
+    }
+}
+
+impl Cell for u16 {
+    const ZERO: Self = 0;
+    const MAX: Self = u16::MAX;
+
+    fn add_signed(self, n: i8) -> Self {
+        self.wrapping_add_signed(n.into())
+    }
+
+    fn multiply_add(self, value: Self, factor: i8) -> Self {
+        self.wrapping_add(value.wrapping_mul(factor as u16))
+    }
+
+    fn low_byte(self) -> u8 {
+        self as u8
+    }
+}
+
+impl Cell for u32 {
+    const ZERO: Self = 0;
+    const MAX: Self = u32::MAX;
+
+    fn add_signed(self, n: i8) -> Self {
+        self.wrapping_add_signed(n.into())
+    }
+
+    fn multiply_add(self, value: Self, factor: i8) -> Self {
+        self.wrapping_add(value.wrapping_mul(factor as u32))
+    }
+
+    fn low_byte(self) -> u8 {
+        self as u8
+    }
+}
+
+/// Receives every tape access made by an [`Interpreter`], e.g. for data-flow tracing or custom
+/// visualizations. All methods default to doing nothing.
+pub trait TapeObserver<C = u8> {
+    /// A cell was read, e.g. by `.`, the test of a loop, or before `+`/`-`.
+    fn read(&mut self, _index: usize, _value: C) {}
+
+    /// A cell was written by `+`, `-` or `,`.
+    fn write(&mut self, _index: usize, _value: C) {}
+
+    /// The pointer was moved by `>` or `<`.
+    fn moved(&mut self, _from: usize, _to: usize) {}
+}
+
+/// A destination for the output of `.` that holds some of it back until the program ends, like
+/// the last partial line of a [`crate::hexdump::HexDump`]. Any other writer is only flushed.
+pub trait FinishOutput: Write {
+    /// Writes everything held back and flushes. Called once the program finished or failed.
+    fn finish(&mut self) -> io::Result<()>;
+}
+
+/// A writer with nothing to hold back.
+struct Flushed<W>(W);
+
+impl<W: Write> Write for Flushed<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+impl<W: Write> FinishOutput for Flushed<W> {
+    fn finish(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+/// Executes programs on a tape of `C` cells.
+pub struct Interpreter<'io, C: Cell = u8> {
     max_array_size: usize,
     eof_behaviour: EofBehaviour,
-    state: State,
+    state: State<C>,
+    /// How often each value was written.
+    value_histogram: Option<BTreeMap<C, u64>>,
+    profile: Option<Profile>,
+    deterministic_input: bool,
+    trim_output: Option<TrimOutput>,
+    /// Null bytes that were held back, as they might turn out to be trailing.
+    pending_nulls: usize,
+    output_started: bool,
+    observer: Option<Box<dyn TapeObserver<C>>>,
+    /// Cells shown on either side of the pointer by `#`.
+    debug_window: usize,
+    flat: bool,
+    pointer_wrap: bool,
+    signed_tape: bool,
+    /// Whether the array was allocated at its full size, so that moves never grow it.
+    fixed_tape: bool,
+    max_cell_value: C,
+    wide_output: WideOutput,
+    strict_ascii_input: bool,
+    input_mode: InputMode,
+    /// Rest of the current line in [`InputMode::Line`].
+    line_buffer: VecDeque<u8>,
+    /// Number of bytes read by `,` so far.
+    input_position: usize,
+    /// Index of the next instruction when executing a [`FlatProgram`].
+    pc: usize,
+    /// Whether no cell has been modified yet.
+    tape_is_zero: bool,
+    max_steps: Option<u64>,
+    /// Number of instructions and loop iterations executed so far.
+    steps: u64,
+    /// Indices of the instruction that failed and of the loops around it, innermost first, to
+    /// look up its source position.
+    failed_instruction: Option<Vec<usize>>,
+    /// Source for `,`, or `None` to read from stdin.
+    input: Option<Box<dyn BufRead + 'io>>,
+    /// Callback polled for the input of `,` instead of reading from `input`.
+    poll_input: Option<Box<dyn FnMut() -> Poll<Option<u8>> + 'io>>,
+    /// Result of the last poll that was ready, if it hasn't been read yet.
+    polled_input: Option<Option<u8>>,
+    /// Sink for `.`, buffered stdout by default. It is flushed before `,` and `#`, and whenever a
+    /// run ends or yields.
+    output: Box<dyn FinishOutput + 'io>,
+}
+
+/// Result of [`Interpreter::run_for`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunStatus {
+    /// The step budget ran out before the program ended.
+    Yielded,
+    Finished,
+    /// The program is at a `,` and the callback of [`Interpreter::with_polled_input`] returned
+    /// [`Poll::Pending`]. Running it again polls the callback again.
+    WaitingForInput,
 }
 
 #[derive(Debug)]
-struct State {
-    array: Vec<u8>,
+struct State<C> {
+    array: Vec<C>,
     pointer: usize,
+    /// Index of the starting cell, which moves right when the array grows at the front.
+    origin: usize,
 }
 
-impl Interpreter {
+impl<'io> Interpreter<'io> {
+    /// Creates an interpreter with 8-bit cells. The tape starts with a single cell and grows as
+    /// the pointer moves right, up to `max_array_size` cells.
+    ///
+    /// ```
+    /// use brainlift::{EofBehaviour, Interpreter, Program, optimizer};
+    ///
+    /// // the last cell is usable whether the growth reaches it in single steps or a larger move,
+    /// // and the one after it is out of bounds
+    /// for max_array_size in 1..=17 {
+    ///     for source in [">".repeat(max_array_size - 1), "><>".repeat(max_array_size - 1)] {
+    ///         for fold in [false, true] {
+    ///             let run = |source: String| {
+    ///                 let mut program = Program::parse(&source).unwrap();
+    ///                 if fold {
+    ///                     optimizer::fold_moves(&mut program);
+    ///                 }
+    ///                 let mut interpreter =
+    ///                     Interpreter::new(max_array_size, EofBehaviour::Ignore);
+    ///                 interpreter
+    ///                     .run(&program)
+    ///                     .map(|()| (interpreter.pointer(), interpreter.current_cell()))
+    ///             };
+    ///
+    ///             assert_eq!(run(format!("{source}+")).unwrap(), (max_array_size - 1, 1));
+    ///             assert!(run(format!("{source}>")).is_err());
+    ///         }
+    ///     }
+    /// }
+    /// ```
     pub fn new(max_array_size: usize, eof_behaviour: EofBehaviour) -> Self {
+        Self::new_with_cells(max_array_size, eof_behaviour)
+    }
+}
+
+impl<'io, C: Cell> Interpreter<'io, C> {
+    /// Creates an interpreter whose cells are `C`, e.g. `Interpreter::<u16>::new_with_cells` for
+    /// 16-bit cells.
+    pub fn new_with_cells(max_array_size: usize, eof_behaviour: EofBehaviour) -> Self {
         Self {
             max_array_size,
             eof_behaviour,
             state: State {
-                array: vec![0; 1],
+                array: vec![C::ZERO; 1],
                 pointer: 0,
+                origin: 0,
             },
+            value_histogram: None,
+            profile: None,
+            deterministic_input: false,
+            trim_output: None,
+            pending_nulls: 0,
+            output_started: false,
+            observer: None,
+            debug_window: DEFAULT_DEBUG_WINDOW,
+            flat: false,
+            pointer_wrap: false,
+            signed_tape: false,
+            fixed_tape: false,
+            max_cell_value: C::MAX,
+            wide_output: WideOutput::LowByte,
+            strict_ascii_input: false,
+            input_mode: InputMode::Byte,
+            line_buffer: VecDeque::new(),
+            input_position: 0,
+            pc: 0,
+            tape_is_zero: true,
+            max_steps: None,
+            steps: 0,
+            failed_instruction: None,
+            input: None,
+            poll_input: None,
+            polled_input: None,
+            output: Box::new(Flushed(BufWriter::new(io::stdout()))),
+        }
+    }
+
+    /// Read the input for `,` from `input` and write the output of `.` to `output`, instead of
+    /// using stdin and stdout.
+    pub fn with_io(mut self, input: impl Read + 'io, output: impl Write + 'io) -> Self {
+        self.input = Some(Box::new(BufReader::new(input)));
+        self.output = Box::new(Flushed(output));
+        self
+    }
+
+    /// Show `radius` cells on either side of the pointer when executing `#`.
+    pub fn with_debug_window(mut self, radius: usize) -> Self {
+        self.debug_window = radius;
+        self
+    }
+
+    /// Execute a [`FlatProgram`] using a precomputed jump table instead of walking the nested
+    /// loops recursively.
+    pub fn with_flat(mut self, enabled: bool) -> Self {
+        self.flat = enabled;
+        self
+    }
+
+    /// Wrap the pointer around the ends of the tape instead of failing with
+    /// [`RuntimeError::PointerOutOfBounds`] when it moves out of bounds.
+    ///
+    /// ```
+    /// use brainlift::{EofBehaviour, Interpreter, Parser, parser};
+    ///
+    /// // keeps the tape of the program it runs at the far end of its own
+    /// let source = std::fs::read_to_string("examples/selfinterpreter.b").unwrap();
+    /// let program = Parser::new(&source).parse().unwrap();
+    /// let hello = std::fs::read_to_string("examples/helloworld.b").unwrap();
+    /// let mut input = parser::minify(&hello);
+    /// input.push(b'!');
+    ///
+    /// let mut output = Vec::new();
+    /// Interpreter::new(65536, EofBehaviour::Ignore)
+    ///     .with_pointer_wrap(true)
+    ///     .with_io(&input[..], &mut output)
+    ///     .run(&program)
+    ///     .unwrap();
+    /// assert_eq!(output, b"Hello World!\n");
+    /// ```
+    pub fn with_pointer_wrap(mut self, enabled: bool) -> Self {
+        self.pointer_wrap = enabled;
+        self
+    }
+
+    /// Let the pointer move left of the starting cell, treating the tape as infinite in both
+    /// directions. The array then also grows at the front, up to `max_array_size` cells in
+    /// total, so indices into [`Interpreter::tape`] shift by the cells that it grew by.
+    ///
+    /// ```
+    /// use brainlift::{EofBehaviour, Interpreter, Parser};
+    ///
+    /// let program = Parser::new("<<+++>>+").parse().unwrap();
+    /// let mut interpreter = Interpreter::new(100, EofBehaviour::Ignore).with_signed_tape(true);
+    /// interpreter.run(&program).unwrap();
+    ///
+    /// assert_eq!(interpreter.position(), 0);
+    /// assert_eq!(interpreter.tape()[interpreter.origin() - 2], 3);
+    /// assert_eq!(interpreter.current_cell(), 1);
+    /// ```
+    pub fn with_signed_tape(mut self, enabled: bool) -> Self {
+        self.signed_tape = enabled;
+        self
+    }
+
+    /// Allocate all `max_array_size` cells up front, like compiled programs do, instead of
+    /// growing the tape as the pointer moves. Moves are then only checked against the ends of
+    /// the tape, which saves the check whether to grow it, e.g. when benchmarking. A fixed tape
+    /// doesn't grow at the front either, so it can't be combined with a signed tape.
+    ///
+    /// ```
+    /// use brainlift::{EofBehaviour, Interpreter, Parser};
+    ///
+    /// let program = Parser::new("+>>>>+").parse().unwrap();
+    /// let mut interpreter = Interpreter::new(5, EofBehaviour::Ignore).with_fixed_tape(true);
+    /// interpreter.run(&program).unwrap();
+    /// assert_eq!(interpreter.tape(), [1, 0, 0, 0, 1]);
+    ///
+    /// let program = Parser::new(">>>>>").parse().unwrap();
+    /// let mut interpreter = Interpreter::new(5, EofBehaviour::Ignore).with_fixed_tape(true);
+    /// assert!(interpreter.run(&program).is_err());
+    /// ```
+    pub fn with_fixed_tape(mut self, enabled: bool) -> Self {
+        self.fixed_tape = enabled;
+        if enabled {
+            self.state.array.resize(self.max_array_size, C::ZERO);
+        }
+        self
+    }
+
+    /// Choose what `.` writes for cells wider than 8 bits: the lowest byte of the value by
+    /// default, or the value itself if it fits in a byte and [`RuntimeError::UnprintableValue`]
+    /// otherwise, or the value encoded as a UTF-8 character. 8-bit cells are always written as
+    /// they are.
+    ///
+    /// ```
+    /// use brainlift::{EofBehaviour, Interpreter, Parser, cli::WideOutput};
+    /// use brainlift::interpreter::RuntimeError;
+    ///
+    /// // writes 321, U+0141 as a character
+    /// let program = Parser::new("++++++++++++++++[>++++++++++++++++++++<-]>+.").parse().unwrap();
+    /// let run = |wide_output| {
+    ///     let mut output = Vec::new();
+    ///     let result = Interpreter::<u16>::new_with_cells(10, EofBehaviour::Ignore)
+    ///         .with_wide_output(wide_output)
+    ///         .with_io(std::io::empty(), &mut output)
+    ///         .run(&program);
+    ///     result.map(|()| output)
+    /// };
+    ///
+    /// assert_eq!(run(WideOutput::LowByte).unwrap(), b"A");
+    /// assert_eq!(run(WideOutput::Unicode).unwrap(), "\u{141}".as_bytes());
+    /// let error = run(WideOutput::Error).unwrap_err();
+    /// assert!(matches!(error, RuntimeError::UnprintableValue { value: 321, .. }));
+    /// ```
+    pub fn with_wide_output(mut self, wide_output: WideOutput) -> Self {
+        self.wide_output = wide_output;
+        self
+    }
+
+    /// Fail with [`RuntimeError::CellValueTooLarge`] when a value above `max` is written to a
+    /// cell, to catch programs violating their own invariants (e.g. a digit cell exceeding 9).
+    ///
+    /// ```
+    /// use brainlift::{EofBehaviour, Interpreter, Parser, interpreter::RuntimeError};
+    ///
+    /// let program = Parser::new("+++>\n++++").with_spans(true).parse().unwrap();
+    /// let mut interpreter = Interpreter::new(10, EofBehaviour::Ignore).with_max_cell_value(3);
+    /// let error = interpreter.run(&program).unwrap_err();
+    /// assert!(matches!(
+    ///     error,
+    ///     RuntimeError::CellValueTooLarge { value: 4, max: 3, position: 1, .. }
+    /// ));
+    /// assert_eq!(
+    ///     error.to_string(),
+    ///     "value 4 written to cell 1 exceeds the maximum of 3 at line 2, column 4"
+    /// );
+    /// ```
+    pub fn with_max_cell_value(mut self, max: C) -> Self {
+        self.max_cell_value = max;
+        self
+    }
+
+    /// Fail with [`RuntimeError::NonAsciiInput`] when `,` reads a byte outside of ASCII.
+    ///
+    /// ```
+    /// use brainlift::{EofBehaviour, Interpreter, Parser, interpreter::RuntimeError};
+    ///
+    /// let program = Parser::new(",.,.").parse().unwrap();
+    /// let mut output = Vec::new();
+    /// let error = Interpreter::new(10, EofBehaviour::Ignore)
+    ///     .with_strict_ascii_input(true)
+    ///     .with_io(&b"a\xff"[..], &mut output)
+    ///     .run(&program)
+    ///     .unwrap_err();
+    /// assert!(matches!(
+    ///     error,
+    ///     RuntimeError::NonAsciiInput { value: 0xff, position: 1, .. }
+    /// ));
+    /// assert_eq!(output, b"a");
+    /// ```
+    pub fn with_strict_ascii_input(mut self, enabled: bool) -> Self {
+        self.strict_ascii_input = enabled;
+        self
+    }
+
+    /// Read input a line at a time instead of a byte at a time, see [`InputMode`].
+    ///
+    /// ```
+    /// use brainlift::{EofBehaviour, Interpreter, Parser, cli::InputMode};
+    ///
+    /// // every line is passed on with its newline, including an unterminated last one
+    /// let program = Parser::new(",[.,]").parse().unwrap();
+    /// let mut output = Vec::new();
+    /// Interpreter::new(10, EofBehaviour::Zero)
+    ///     .with_input_mode(InputMode::Line)
+    ///     .with_io(&b"first\n\nthird\nlast"[..], &mut output)
+    ///     .run(&program)
+    ///     .unwrap();
+    /// assert_eq!(output, b"first\n\nthird\nlast");
+    /// ```
+    pub fn with_input_mode(mut self, input_mode: InputMode) -> Self {
+        self.input_mode = input_mode;
+        self
+    }
+
+    /// Read the input for `,` from `input` instead of stdin.
+    pub fn with_input(mut self, input: impl Read + 'io) -> Self {
+        self.input = Some(Box::new(BufReader::new(input)));
+        self
+    }
+
+    /// Take the input for `,` from `poll` instead of stdin, which returns [`Poll::Ready`] with the
+    /// next byte or `None` at EOF, or [`Poll::Pending`] if no input is available yet. This lets
+    /// the interpreter be driven from an event loop or an async task: [`Interpreter::run_for`]
+    /// then returns [`RunStatus::WaitingForInput`] instead of blocking. The other ways to run a
+    /// program keep polling until input is ready. Bytes are passed to `,` as they are polled,
+    /// regardless of [`Interpreter::with_input_mode`].
+    ///
+    /// ```
+    /// use std::{cell::RefCell, collections::VecDeque, task::Poll};
+    ///
+    /// use brainlift::{EofBehaviour, Interpreter, Parser};
+    /// use brainlift::{interpreter::RunStatus, program::FlatProgram};
+    ///
+    /// let program = Parser::new(",[.,]").parse().unwrap();
+    /// let program = FlatProgram::from(&program);
+    /// // input arrives over time, with `None` for EOF, e.g. from a channel
+    /// let queue = RefCell::new(VecDeque::new());
+    /// let mut output = Vec::new();
+    /// let mut interpreter = Interpreter::new(10, EofBehaviour::Zero)
+    ///     .with_polled_input(|| match queue.borrow_mut().pop_front() {
+    ///         Some(input) => Poll::Ready(input),
+    ///         None => Poll::Pending,
+    ///     })
+    ///     .with_output(&mut output);
+    ///
+    /// assert_eq!(interpreter.run_for(&program, 100).unwrap(), RunStatus::WaitingForInput);
+    /// queue.borrow_mut().extend([Some(b'h'), Some(b'i')]);
+    /// assert_eq!(interpreter.run_for(&program, 100).unwrap(), RunStatus::WaitingForInput);
+    /// queue.borrow_mut().push_back(None);
+    /// assert_eq!(interpreter.run_for(&program, 100).unwrap(), RunStatus::Finished);
+    ///
+    /// drop(interpreter);
+    /// assert_eq!(output, b"hi");
+    /// ```
+    pub fn with_polled_input(mut self, poll: impl FnMut() -> Poll<Option<u8>> + 'io) -> Self {
+        self.poll_input = Some(Box::new(poll));
+        self
+    }
+
+    /// Write the output of `.` to `output` instead of stdout.
+    pub fn with_output(mut self, output: impl Write + 'io) -> Self {
+        self.output = Box::new(Flushed(output));
+        self
+    }
+
+    /// Write the output of `.` to `output`, which is finished when the program ends.
+    ///
+    /// ```
+    /// use brainlift::{EofBehaviour, Interpreter, Parser, hexdump::HexDump};
+    ///
+    /// let program = Parser::new("++++++++[>++++++++<-]>+.+.+.").parse().unwrap();
+    /// let mut dump = Vec::new();
+    /// let mut interpreter = Interpreter::new(10, EofBehaviour::Ignore)
+    ///     .with_finishing_output(HexDump::new(&mut dump));
+    /// interpreter.run(&program).unwrap();
+    ///
+    /// // the partial line was written by the run, not by dropping the dump
+    /// std::mem::forget(interpreter);
+    /// assert!(String::from_utf8(dump).unwrap().ends_with("  ABC\n"));
+    /// ```
+    pub fn with_finishing_output(mut self, output: impl FinishOutput + 'io) -> Self {
+        self.output = Box::new(output);
+        self
+    }
+
+    /// Report all tape accesses to `observer`.
+    pub fn with_observer(mut self, observer: impl TapeObserver<C> + 'static) -> Self {
+        self.observer = Some(Box::new(observer));
+        self
+    }
+
+    /// Never block on `,`: if stdin is a terminal or has no pending data, apply the EOF behaviour
+    /// instead. Useful in CI, where a program waiting for input would hang the job.
+    pub fn with_deterministic_input(mut self, enabled: bool) -> Self {
+        self.deterministic_input = enabled;
+        self
+    }
+
+    /// Count how often each value is written to a cell and print a histogram after the run.
+    pub fn with_value_histogram(mut self, enabled: bool) -> Self {
+        self.value_histogram = enabled.then(BTreeMap::new);
+        self
+    }
+
+    /// Count how often each instruction is executed and how often each loop iterates, see
+    /// [`Interpreter::profile`]. Only programs executed as nested loops are profiled, not
+    /// [`FlatProgram`]s.
+    pub fn with_profile(mut self, enabled: bool) -> Self {
+        self.profile = enabled.then(Profile::default);
+        self
+    }
+
+    /// Fail with [`RuntimeError::StepLimitExceeded`] once more than `max_steps` instructions were
+    /// executed, counting every iteration of a loop as one, e.g. to stop untrusted programs that
+    /// never end.
+    ///
+    /// ```
+    /// use brainlift::{EofBehaviour, Interpreter, Parser, interpreter::RuntimeError};
+    ///
+    /// let program = Parser::new("+[]").parse().unwrap();
+    /// let error = Interpreter::new(30_000, EofBehaviour::Ignore)
+    ///     .with_max_steps(Some(1000))
+    ///     .run(&program)
+    ///     .unwrap_err();
+    /// assert!(matches!(error, RuntimeError::StepLimitExceeded { limit: 1000, .. }));
+    ///
+    /// // leading loops are skipped while the tape is known to be zero, but their tests still
+    /// // count as one step each
+    /// let program = Parser::new("[-][.]+.").parse().unwrap();
+    /// for flat in [false, true] {
+    ///     let run = |max_steps| {
+    ///         Interpreter::new(30_000, EofBehaviour::Ignore)
+    ///             .with_io(&b""[..], std::io::sink())
+    ///             .with_flat(flat)
+    ///             .with_max_steps(Some(max_steps))
+    ///             .run(&program)
+    ///     };
+    ///     assert!(run(3).is_err());
+    ///     assert!(run(4).is_ok());
+    /// }
+    /// ```
+    pub fn with_max_steps(mut self, max_steps: Option<u64>) -> Self {
+        self.max_steps = max_steps;
+        self
+    }
+
+    /// Strip null bytes from the end (and optionally the start) of the program's output.
+    /// Note that this alters the program's output, so it is off by default.
+    pub fn with_trim_output(mut self, trim_output: Option<TrimOutput>) -> Self {
+        self.trim_output = trim_output;
+        self
+    }
+
+    pub fn run(&mut self, program: &Program) -> Result<(), RuntimeError> {
+        let result = if self.flat {
+            self.run_flat(&FlatProgram::from(program))
+        } else {
+            self.run_nested(program).map_err(|e| {
+                let path = self.failed_instruction.take().map(|mut path| {
+                    path.reverse();
+                    path
+                });
+                e.with_span(path.and_then(|path| program.span_at(&path)))
+            })
+        };
+
+        let flushed = self.output.finish();
+
+        if let Some(histogram) = &self.value_histogram {
+            Self::print_histogram(histogram);
+        }
+
+        result?;
+        flushed.map_err(RuntimeError::Io)
+    }
+
+    fn run_nested(&mut self, program: &Program) -> Result<(), RuntimeError> {
+        // Until the first cell is modified the whole tape is zero, so leading loops (commonly
+        // used as comment blocks) can be skipped without evaluating their condition.
+        for (index, instruction) in program.instructions.iter().enumerate() {
+            match instruction {
+                // the skipped test still counts as a step and as executing the loop
+                Loop(_) if self.tape_is_zero => {
+                    if let Some(profile) = &mut self.profile {
+                        profile.execute(instruction);
+                    }
+                    self.count_step()
+                        .inspect_err(|_| self.failed_instruction = Some(vec![index]))?;
+                    continue;
+                }
+                Increment | Decrement | Add(_) | Set(_) | Input | InitCells { .. } => {
+                    self.tape_is_zero = false
+                }
+                _ => {}
+            }
+            self.execute_instruction(instruction)
+                .inspect_err(|_| self.fail_within(index))?;
+        }
+
+        Ok(())
+    }
+
+    fn run_flat(&mut self, program: &FlatProgram) -> Result<(), RuntimeError> {
+        loop {
+            match self.run_for(program, u64::MAX)? {
+                RunStatus::Yielded => {}
+                RunStatus::Finished => return Ok(()),
+                RunStatus::WaitingForInput => thread::yield_now(),
+            }
+        }
+    }
+
+    /// Executes at most `steps` instructions of `program` and returns whether it finished. The
+    /// interpreter keeps its position, so calling this again resumes where it yielded, e.g. to
+    /// run several programs round-robin. Each call must be given the same program.
+    ///
+    /// ```
+    /// use brainlift::{EofBehaviour, Interpreter, Parser};
+    /// use brainlift::{interpreter::RunStatus, program::FlatProgram};
+    ///
+    /// let first = Parser::new("+++[.-]").parse().unwrap();
+    /// let second = Parser::new("++[.-]").parse().unwrap();
+    /// let (first, second) = (FlatProgram::from(&first), FlatProgram::from(&second));
+    /// let output = std::cell::RefCell::new(Vec::new());
+    /// let writer = || Writer(&output);
+    /// let mut interpreters = [
+    ///     (Interpreter::new(10, EofBehaviour::Ignore).with_output(writer()), &first),
+    ///     (Interpreter::new(10, EofBehaviour::Ignore).with_output(writer()), &second),
+    /// ];
+    ///
+    /// // three steps at a time, until both have finished
+    /// let mut running = true;
+    /// while running {
+    ///     running = false;
+    ///     for (interpreter, program) in &mut interpreters {
+    ///         running |= interpreter.run_for(program, 3).unwrap() == RunStatus::Yielded;
+    ///     }
+    /// }
+    /// assert_eq!(*output.borrow(), [3, 2, 2, 1, 1]);
+    ///
+    /// struct Writer<'a>(&'a std::cell::RefCell<Vec<u8>>);
+    ///
+    /// impl std::io::Write for Writer<'_> {
+    ///     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+    ///         self.0.borrow_mut().write(buf)
+    ///     }
+    ///
+    ///     fn flush(&mut self) -> std::io::Result<()> {
+    ///         Ok(())
+    ///     }
+    /// }
+    /// ```
+    pub fn run_for(
+        &mut self,
+        program: &FlatProgram,
+        steps: u64,
+    ) -> Result<RunStatus, RuntimeError> {
+        let mut result = Ok(());
+        for _ in 0..steps {
+            let Some(instruction) = program.instructions.get(self.pc) else {
+                break;
+            };
+            if let FlatInstruction::Input = instruction
+                && !self.input_ready()
+            {
+                return self
+                    .end_slice(program, Ok(()))
+                    .map(|_| RunStatus::WaitingForInput);
+            }
+            result = self.step_flat(program, instruction);
+            if result.is_err() {
+                break;
+            }
+        }
+
+        self.end_slice(program, result)
+    }
+
+    /// Flushes the output after running part of `program`, or finishes it if the program ended
+    /// or failed with `result`.
+    fn end_slice(
+        &mut self,
+        program: &FlatProgram,
+        result: Result<(), RuntimeError>,
+    ) -> Result<RunStatus, RuntimeError> {
+        let status = if self.pc < program.instructions.len() {
+            RunStatus::Yielded
+        } else {
+            RunStatus::Finished
+        };
+        let flushed = if result.is_ok() && status == RunStatus::Yielded {
+            self.output.flush()
+        } else {
+            self.output.finish()
+        };
+
+        result?;
+        flushed?;
+        Ok(status)
+    }
+
+    /// Executes `program` up to and including the next `#`, which stops execution instead of
+    /// printing the state. Returns [`RunStatus::Yielded`] if it stopped at a `#`.
+    pub fn run_to_breakpoint(&mut self, program: &FlatProgram) -> Result<RunStatus, RuntimeError> {
+        while let Some(instruction) = program.instructions.get(self.pc) {
+            if let FlatInstruction::Debug = instruction {
+                self.pc += 1;
+                self.output.flush()?;
+                return Ok(RunStatus::Yielded);
+            }
+            if let Err(e) = self.step_flat(program, instruction) {
+                return self.end_slice(program, Err(e));
+            }
         }
+
+        self.end_slice(program, Ok(()))
     }
 
-    pub fn run(&mut self, program: &Program) {
-        for instruction in &program.instructions {
-            self.execute_instruction(instruction);
+    /// Executes the next instruction of `program` and returns whether the program finished. A
+    /// `#` is skipped without printing the state, as stepping is meant for debuggers, for which
+    /// it is a breakpoint.
+    pub fn step(&mut self, program: &FlatProgram) -> Result<RunStatus, RuntimeError> {
+        let result = match program.instructions.get(self.pc) {
+            Some(FlatInstruction::Debug) => {
+                self.pc += 1;
+                Ok(())
+            }
+            Some(instruction) => self.step_flat(program, instruction),
+            None => Ok(()),
+        };
+
+        self.end_slice(program, result)
+    }
+
+    /// Returns the index of the instruction executed next when running a [`FlatProgram`].
+    pub fn next_instruction(&self) -> usize {
+        self.pc
+    }
+
+    fn step_flat(
+        &mut self,
+        program: &FlatProgram,
+        instruction: &FlatInstruction,
+    ) -> Result<(), RuntimeError> {
+        self.count_step()
+            .and_then(|()| self.execute_flat(program, instruction))
+            .map_err(|e| e.with_span(program.span(self.pc)))?;
+
+        if let FlatInstruction::Increment
+        | FlatInstruction::Decrement
+        | FlatInstruction::Add(_)
+        | FlatInstruction::Set(_)
+        | FlatInstruction::Input
+        | FlatInstruction::InitCells { .. } = instruction
+        {
+            self.tape_is_zero = false;
         }
+        self.pc += 1;
+
+        Ok(())
     }
 
-    fn execute_instruction(&mut self, instruction: &Instruction) {
+    fn execute_flat(
+        &mut self,
+        program: &FlatProgram,
+        instruction: &FlatInstruction,
+    ) -> Result<(), RuntimeError> {
         match instruction {
-            Debug => {
-                println!("{:?}", self.state);
+            FlatInstruction::Increment => self.increment()?,
+            FlatInstruction::Decrement => self.decrement()?,
+            FlatInstruction::Add(n) => self.add(*n)?,
+            FlatInstruction::Right => self.right()?,
+            FlatInstruction::Left => self.left()?,
+            FlatInstruction::Move(offset) => self.move_by(*offset)?,
+            FlatInstruction::SetZero => self.write(C::ZERO)?,
+            FlatInstruction::Set(value) => self.write(C::from(*value))?,
+            FlatInstruction::Output => self.output()?,
+            FlatInstruction::OutputBytes(bytes) => self.output_bytes(bytes)?,
+            FlatInstruction::InitCells {
+                base_offset,
+                values,
+            } => self.init_cells(*base_offset, values)?,
+            FlatInstruction::MultiplyAdd { targets } => self.multiply_add(targets)?,
+            FlatInstruction::Scan { step } => self.scan(*step)?,
+            FlatInstruction::Input => self.input()?,
+            FlatInstruction::Debug => self.debug()?,
+            FlatInstruction::LoopStart => {
+                // see `run_nested`, this skips leading loops the same way
+                if self.tape_is_zero || self.read() == C::ZERO {
+                    self.pc = program.jumps[self.pc];
+                }
+            }
+            FlatInstruction::LoopEnd => {
+                if self.read() != C::ZERO {
+                    self.pc = program.jumps[self.pc];
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the tape, with trailing cells that are zero because the tape hasn't been grown
+    /// that far yet omitted.
+    ///
+    /// ```
+    /// use brainlift::{EofBehaviour, Instruction, Interpreter, Program};
+    ///
+    /// // a single move far past the cells the tape has grown to so far
+    /// let program = Program {
+    ///     instructions: vec![
+    ///         Instruction::Increment,
+    ///         Instruction::Move(50),
+    ///         Instruction::Increment,
+    ///     ],
+    ///     tape_size: None,
+    ///     spans: None,
+    ///     noopt: None,
+    ///     input: None,
+    /// };
+    /// let mut interpreter = Interpreter::new(51, EofBehaviour::Ignore);
+    /// interpreter.run(&program).unwrap();
+    /// assert_eq!(interpreter.tape().len(), 51);
+    /// assert_eq!((interpreter.tape()[0], interpreter.tape()[50]), (1, 1));
+    ///
+    /// let mut interpreter = Interpreter::new(50, EofBehaviour::Ignore);
+    /// assert!(interpreter.run(&program).is_err());
+    /// ```
+    pub fn tape(&self) -> &[C] {
+        let len = self
+            .state
+            .array
+            .iter()
+            .rposition(|&c| c != C::ZERO)
+            .map_or(0, |i| i + 1);
+        &self.state.array[..len]
+    }
+
+    /// Returns the counts collected so far, if profiling is enabled.
+    pub fn profile(&self) -> Option<&Profile> {
+        self.profile.as_ref()
+    }
+
+    /// Returns the index of the pointer in [`Interpreter::tape`].
+    pub fn pointer(&self) -> usize {
+        self.state.pointer
+    }
+
+    /// Returns the index of the starting cell in [`Interpreter::tape`], which is only nonzero
+    /// once a [signed tape](Interpreter::with_signed_tape) grew at the front.
+    pub fn origin(&self) -> usize {
+        self.state.origin
+    }
+
+    /// Returns the position of the pointer relative to the starting cell, which is negative left
+    /// of it on a [signed tape](Interpreter::with_signed_tape).
+    pub fn position(&self) -> isize {
+        self.state.pointer as isize - self.state.origin as isize
+    }
+
+    /// Returns the value of the cell under the pointer.
+    pub fn current_cell(&self) -> C {
+        self.state
+            .array
+            .get(self.state.pointer)
+            .copied()
+            .unwrap_or(C::ZERO)
+    }
+
+    /// Renders the cells up to `radius` cells left and right of the pointer as a table of their
+    /// positions, decimal values and ASCII characters, with the pointer's position in brackets.
+    pub fn tape_window(&self, radius: usize) -> String {
+        let pointer = self.state.pointer;
+        let last = pointer.saturating_add(radius).min(self.max_array_size - 1);
+
+        let mut rows = [
+            String::from("cell "),
+            String::from("dec  "),
+            String::from("ascii"),
+        ];
+        for index in pointer.saturating_sub(radius)..=last {
+            let value = self.state.array.get(index).copied().unwrap_or(C::ZERO);
+            let position = index as isize - self.state.origin as isize;
+            let label = if index == pointer {
+                format!("[{position}]")
+            } else {
+                position.to_string()
+            };
+            let decimal = value.to_string();
+            let ascii = char::from_u32(value.into())
+                .filter(char::is_ascii_graphic)
+                .unwrap_or('.');
+
+            let width = label.len().max(decimal.len()).max(3);
+            rows[0].push_str(&format!("  {label:>width$}"));
+            rows[1].push_str(&format!("  {decimal:>width$}"));
+            rows[2].push_str(&format!("  {ascii:>width$}"));
+        }
+        rows.join("\n")
+    }
+
+    fn print_histogram(histogram: &BTreeMap<C, u64>) {
+        const BAR_WIDTH: u64 = 40;
+
+        let max = histogram.values().copied().max().unwrap_or(0).max(1);
+        eprintln!("value      writes");
+        for (value, &count) in histogram {
+            let bar = "#".repeat((count * BAR_WIDTH).div_ceil(max) as usize);
+            eprintln!("{value:>5} {count:>11} {bar}");
+        }
+    }
+
+    fn execute_instruction(&mut self, instruction: &Instruction) -> Result<(), RuntimeError> {
+        if let Some(profile) = &mut self.profile {
+            profile.execute(instruction);
+        }
+
+        if let Loop(body) = instruction {
+            return self.loop_(instruction, body);
+        }
+        self.count_step()
+            .and_then(|()| self.execute(instruction))
+            .inspect_err(|_| self.failed_instruction = Some(Vec::new()))
+    }
+
+    /// Records that the failed instruction is at `index` of the instructions being executed.
+    fn fail_within(&mut self, index: usize) {
+        if let Some(path) = &mut self.failed_instruction {
+            path.push(index);
+        }
+    }
+
+    /// Counts an executed instruction or loop iteration against [`Interpreter::with_max_steps`].
+    fn count_step(&mut self) -> Result<(), RuntimeError> {
+        self.steps += 1;
+        match self.max_steps {
+            Some(limit) if self.steps > limit => {
+                Err(RuntimeError::StepLimitExceeded { limit, span: None })
             }
-            Increment => self.increment(),
-            Decrement => self.decrement(),
-            Right => self.right(),
-            Left => self.left(),
-            Output => self.output(),
-            Input => self.input(),
-            Loop(instructions) => self.loop_(instructions),
+            _ => Ok(()),
+        }
+    }
+
+    /// Executes an instruction other than a loop.
+    fn execute(&mut self, instruction: &Instruction) -> Result<(), RuntimeError> {
+        match instruction {
+            Debug => self.debug()?,
+            Increment => self.increment()?,
+            Decrement => self.decrement()?,
+            Add(n) => self.add(*n)?,
+            Right => self.right()?,
+            Left => self.left()?,
+            Move(offset) => self.move_by(*offset)?,
+            SetZero => self.write(C::ZERO)?,
+            Set(value) => self.write(C::from(*value))?,
+            Output => self.output()?,
+            OutputBytes(bytes) => self.output_bytes(bytes)?,
+            InitCells {
+                base_offset,
+                values,
+            } => self.init_cells(*base_offset, values)?,
+            MultiplyAdd { targets } => self.multiply_add(targets)?,
+            Scan { step } => self.scan(*step)?,
+            Input => self.input()?,
+            Loop(_) => unreachable!("loops are executed by `execute_instruction`"),
         }
+
+        Ok(())
+    }
+
+    fn debug(&mut self) -> io::Result<()> {
+        // written to the program's output, so that it stays in order with it
+        let window = self.tape_window(self.debug_window);
+        writeln!(self.output, "{window}")
+    }
+
+    fn increment(&mut self) -> Result<(), RuntimeError> {
+        let value = self.read().add_signed(1);
+        self.write(value)
     }
 
-    fn increment(&mut self) {
-        *self.current() = self.current().wrapping_add(1)
+    fn decrement(&mut self) -> Result<(), RuntimeError> {
+        let value = self.read().add_signed(-1);
+        self.write(value)
     }
 
-    fn decrement(&mut self) {
-        *self.current() = self.current().wrapping_sub(1)
+    fn add(&mut self, n: i8) -> Result<(), RuntimeError> {
+        let value = self.read().add_signed(n);
+        self.write(value)
     }
 
-    fn right(&mut self) {
-        let index = self.state.pointer + 1;
-        if index >= self.max_array_size {
-            panic!("tried to move rightwards out-of-bounds");
+    fn right(&mut self) -> Result<(), RuntimeError> {
+        self.move_by(1)
+    }
+
+    fn left(&mut self) -> Result<(), RuntimeError> {
+        self.move_by(-1)
+    }
+
+    fn move_by(&mut self, offset: isize) -> Result<(), RuntimeError> {
+        let index = self.cell_index(offset)?;
+        self.moved(index);
+        Ok(())
+    }
+
+    /// Returns the index of the cell `offset` cells from the pointer, growing the array to
+    /// include it. Single steps and larger moves grow it the same way, doubling its size until
+    /// it covers the cell, so that moving across the tape takes amortized constant time.
+    fn cell_index(&mut self, offset: isize) -> Result<usize, RuntimeError> {
+        if self.fixed_tape && !self.pointer_wrap {
+            // the array already covers the whole tape, so there is nothing to grow
+            return match self.state.pointer.checked_add_signed(offset) {
+                Some(index) if index < self.state.array.len() => Ok(index),
+                _ if offset < 0 => Err(RuntimeError::out_of_bounds(Direction::Left)),
+                _ => Err(RuntimeError::out_of_bounds(Direction::Right)),
+            };
         }
 
-        // grow array if necessary and possible
+        let index = if self.pointer_wrap {
+            let index = (self.state.pointer as i128 + offset as i128)
+                .rem_euclid(self.max_array_size as i128);
+            index as usize
+        } else {
+            let index = match self.state.pointer.checked_add_signed(offset) {
+                Some(index) => index,
+                None => {
+                    let distance = offset.unsigned_abs();
+                    self.grow_front(distance - self.state.pointer)?;
+                    self.state.pointer - distance
+                }
+            };
+            if index >= self.max_array_size {
+                return Err(RuntimeError::out_of_bounds(Direction::Right));
+            }
+            index
+        };
+
+        if index >= self.state.array.len() {
+            let new_size = min(self.max_array_size, (index + 1).next_power_of_two());
+            self.state.array.resize(new_size, C::ZERO);
+        }
+
+        Ok(index)
+    }
+
+    /// Prepends at least `needed` cells to the array on a signed tape, keeping the pointer on the
+    /// same cell. Otherwise, there are no cells left of the first one.
+    fn grow_front(&mut self, needed: usize) -> Result<(), RuntimeError> {
         let current_size = self.state.array.len();
-        if self.state.pointer == current_size - 1 && current_size < self.max_array_size {
-            let new_size = min(self.max_array_size, current_size * 2);
-            self.state.array.resize(new_size, 0);
+        if !self.signed_tape || current_size + needed > self.max_array_size {
+            return Err(RuntimeError::out_of_bounds(Direction::Left));
         }
 
-        self.state.pointer = index;
+        let new_size = min(
+            self.max_array_size,
+            (current_size + needed).next_power_of_two(),
+        );
+        let added = new_size - current_size;
+        self.state
+            .array
+            .splice(0..0, iter::repeat_n(C::ZERO, added));
+        self.state.pointer += added;
+        self.state.origin += added;
+        Ok(())
     }
 
-    fn left(&mut self) {
-        if self.state.pointer == 0 {
-            panic!("tried to move leftwards out-of-bounds");
+    fn output(&mut self) -> Result<(), RuntimeError> {
+        let cell = self.read();
+        let value: u32 = cell.into();
+        let unprintable = RuntimeError::UnprintableValue {
+            value,
+            wide_output: self.wide_output,
+            span: None,
+        };
+        if C::MAX.into() == u32::from(u8::MAX) {
+            return Ok(self.put(cell.low_byte())?);
         }
 
-        self.state.pointer -= 1;
+        match self.wide_output {
+            WideOutput::LowByte => self.put(cell.low_byte())?,
+            WideOutput::Error => self.put(u8::try_from(value).map_err(|_| unprintable)?)?,
+            WideOutput::Unicode => {
+                let character = char::from_u32(value).ok_or(unprintable)?;
+                let mut bytes = [0; 4];
+                self.output_bytes(character.encode_utf8(&mut bytes).as_bytes())?;
+            }
+        }
+        Ok(())
+    }
+
+    fn output_bytes(&mut self, bytes: &[u8]) -> io::Result<()> {
+        bytes.iter().try_for_each(|&byte| self.put(byte))
+    }
+
+    fn init_cells(&mut self, base_offset: usize, values: &[u8]) -> Result<(), RuntimeError> {
+        let start = self.state.pointer + base_offset;
+        let end = start + values.len();
+        if end > self.max_array_size {
+            return Err(RuntimeError::out_of_bounds(Direction::Right));
+        }
+        if end > self.state.array.len() {
+            let new_size = min(self.max_array_size, end.next_power_of_two());
+            self.state.array.resize(new_size, C::ZERO);
+        }
+
+        if let Some((offset, &value)) = values
+            .iter()
+            .enumerate()
+            .find(|(_, value)| C::from(**value) > self.max_cell_value)
+        {
+            return Err(RuntimeError::CellValueTooLarge {
+                value: value.into(),
+                max: self.max_cell_value.into(),
+                position: start + offset,
+                span: None,
+            });
+        }
+
+        for (index, &value) in (start..end).zip(values) {
+            let value = C::from(value);
+            self.state.array[index] = value;
+
+            if let Some(observer) = &mut self.observer {
+                observer.write(index, value);
+            }
+            if let Some(histogram) = &mut self.value_histogram {
+                *histogram.entry(value).or_default() += 1;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn multiply_add(&mut self, targets: &[(isize, i8)]) -> Result<(), RuntimeError> {
+        let value = self.read();
+        if value == C::ZERO {
+            return Ok(());
+        }
+
+        for &(offset, factor) in targets {
+            let index = self.cell_index(offset)?;
+            let target = self.state.array[index];
+            if let Some(observer) = &mut self.observer {
+                observer.read(index, target);
+            }
+            self.write_at(index, target.multiply_add(value, factor))?;
+        }
+
+        self.write(C::ZERO)
     }
 
-    fn output(&mut self) {
-        print!("{}", *self.current() as char)
+    /// Moves the pointer by `step` until it is on a zero cell. Single steps over a tape that
+    /// doesn't wrap are searched for directly, as they neither need to report every cell to an
+    /// observer nor count every move as a step. Cells the array hasn't grown to yet are zero.
+    fn scan(&mut self, step: isize) -> Result<(), RuntimeError> {
+        let direct = !self.pointer_wrap && self.observer.is_none() && self.max_steps.is_none();
+        let pointer = self.state.pointer;
+        match step {
+            1 if direct => {
+                let array = &self.state.array;
+                let distance = array[pointer..]
+                    .iter()
+                    .position(|&cell| cell == C::ZERO)
+                    .unwrap_or(array.len() - pointer);
+                self.move_by(distance as isize)
+            }
+            -1 if direct => {
+                let index = self.state.array[..=pointer]
+                    .iter()
+                    .rposition(|&cell| cell == C::ZERO);
+                match index {
+                    Some(index) => {
+                        self.moved(index);
+                        Ok(())
+                    }
+                    None => self.move_by(-(pointer as isize) - 1),
+                }
+            }
+            _ => {
+                while self.read() != C::ZERO {
+                    self.count_step()?;
+                    self.move_by(step)?;
+                }
+                Ok(())
+            }
+        }
     }
 
-    fn input(&mut self) {
-        let input = io::stdin()
-            .lock()
-            .bytes()
-            .next()
-            .transpose()
-            .expect("failed to read from stdin");
+    fn put(&mut self, byte: u8) -> io::Result<()> {
+        if let Some(trim_output) = self.trim_output {
+            if byte == 0 {
+                if self.output_started || trim_output == TrimOutput::Trailing {
+                    self.pending_nulls += 1;
+                }
+                return Ok(());
+            }
+
+            let nulls = vec![0; self.pending_nulls];
+            self.output.write_all(&nulls)?;
+            self.pending_nulls = 0;
+            self.output_started = true;
+        }
+
+        self.output.write_all(&[byte])
+    }
+
+    fn input(&mut self) -> Result<(), RuntimeError> {
+        // make sure a buffered prompt is visible before waiting for input
+        self.output.flush()?;
+
+        let input = match self.input_mode {
+            _ if self.poll_input.is_some() => Ok(self.next_polled_input()),
+            InputMode::Byte => self.read_byte(),
+            InputMode::Line => self.read_line_byte(),
+        }?;
 
         if let Some(input) = input {
-            *self.current() = input;
+            if self.strict_ascii_input && !input.is_ascii() {
+                return Err(RuntimeError::NonAsciiInput {
+                    value: input,
+                    position: self.input_position,
+                    span: None,
+                });
+            }
+            self.input_position += 1;
+            self.write(C::from(input))
         } else {
             match self.eof_behaviour {
-                EofBehaviour::Ignore => {}
-                EofBehaviour::Zero => *self.current() = 0,
+                EofBehaviour::Ignore => Ok(()),
+                EofBehaviour::Zero => self.write(C::ZERO),
+                EofBehaviour::Negative => self.write(C::MAX),
+            }
+        }
+    }
+
+    /// Polls the callback of [`Interpreter::with_polled_input`], if there is one, and returns
+    /// whether input for `,` is ready.
+    fn input_ready(&mut self) -> bool {
+        if let Some(poll) = &mut self.poll_input
+            && self.polled_input.is_none()
+            && let Poll::Ready(input) = poll()
+        {
+            self.polled_input = Some(input);
+        }
+        self.poll_input.is_none() || self.polled_input.is_some()
+    }
+
+    /// Returns the next polled input, polling until it is ready.
+    fn next_polled_input(&mut self) -> Option<u8> {
+        while !self.input_ready() {
+            thread::yield_now();
+        }
+        self.polled_input.take().flatten()
+    }
+
+    fn read_byte(&mut self) -> io::Result<Option<u8>> {
+        match &mut self.input {
+            Some(input) => input.bytes().next().transpose(),
+            None if self.deterministic_input => Self::read_pending_stdin(),
+            None => io::stdin().lock().bytes().next().transpose(),
+        }
+    }
+
+    /// Returns the next byte of the current line, first reading the next line up to and
+    /// including its newline if the current one has been used up.
+    fn read_line_byte(&mut self) -> io::Result<Option<u8>> {
+        if self.line_buffer.is_empty() {
+            while let Some(byte) = self.read_byte()? {
+                self.line_buffer.push_back(byte);
+                if byte == b'\n' {
+                    break;
+                }
+            }
+        }
+        Ok(self.line_buffer.pop_front())
+    }
+
+    /// Reads a byte from stdin if one can be read without blocking. A terminal, or a pipe without
+    /// pending data, is treated as EOF. This bypasses the buffering of [`io::Stdin`], since
+    /// buffered data wouldn't be visible to `poll`.
+    fn read_pending_stdin() -> io::Result<Option<u8>> {
+        // SAFETY: only operates on the stdin file descriptor and a local one-byte buffer
+        unsafe {
+            if libc::isatty(libc::STDIN_FILENO) == 1 {
+                return Ok(None);
+            }
+
+            let mut fds = libc::pollfd {
+                fd: libc::STDIN_FILENO,
+                events: libc::POLLIN,
+                revents: 0,
+            };
+            if libc::poll(&mut fds, 1, 0) < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            if fds.revents == 0 {
+                return Ok(None);
+            }
+
+            let mut byte = 0u8;
+            match libc::read(libc::STDIN_FILENO, (&raw mut byte).cast(), 1) {
+                n if n < 0 => Err(io::Error::last_os_error()),
+                0 => Ok(None),
+                _ => Ok(Some(byte)),
             }
         }
     }
 
-    fn loop_(&mut self, instructions: &[Instruction]) {
-        while *self.current() != 0 {
-            for i in instructions {
-                self.execute_instruction(i);
+    fn loop_(
+        &mut self,
+        instruction: &Instruction,
+        body: &[Instruction],
+    ) -> Result<(), RuntimeError> {
+        while self.read() != C::ZERO {
+            if let Some(profile) = &mut self.profile {
+                profile.iterate(instruction);
             }
+            self.count_step()
+                .inspect_err(|_| self.failed_instruction = Some(Vec::new()))?;
+            for (index, i) in body.iter().enumerate() {
+                self.execute_instruction(i)
+                    .inspect_err(|_| self.fail_within(index))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn moved(&mut self, index: usize) {
+        if let Some(observer) = &mut self.observer {
+            observer.moved(self.state.pointer, index);
+        }
+
+        self.state.pointer = index;
+    }
+
+    fn read(&mut self) -> C {
+        let value = *self.current();
+
+        if let Some(observer) = &mut self.observer {
+            observer.read(self.state.pointer, value);
+        }
+
+        value
+    }
+
+    fn write(&mut self, value: C) -> Result<(), RuntimeError> {
+        self.write_at(self.state.pointer, value)
+    }
+
+    fn write_at(&mut self, index: usize, value: C) -> Result<(), RuntimeError> {
+        if value > self.max_cell_value {
+            return Err(RuntimeError::CellValueTooLarge {
+                value: value.into(),
+                max: self.max_cell_value.into(),
+                position: index,
+                span: None,
+            });
+        }
+
+        self.state.array[index] = value;
+
+        if let Some(observer) = &mut self.observer {
+            observer.write(index, value);
+        }
+
+        if let Some(histogram) = &mut self.value_histogram {
+            *histogram.entry(value).or_default() += 1;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the cell under the pointer. Moves grow the array before the pointer passes its
+    /// end, so it only grows here if that ever went wrong, instead of indexing past the end.
+    fn current(&mut self) -> &mut C {
+        let pointer = self.state.pointer;
+        debug_assert!(
+            pointer < self.max_array_size,
+            "pointer {pointer} is out of bounds"
+        );
+        if pointer >= self.state.array.len() {
+            self.state.array.resize(pointer + 1, C::ZERO);
+        }
+        &mut self.state.array[pointer]
+    }
+}
+
+/// Errors that stop a run. `span` is the source position of the instruction that caused it, if
+/// the program has them.
+#[derive(Debug)]
+pub enum RuntimeError {
+    CellValueTooLarge {
+        value: u32,
+        max: u32,
+        position: usize,
+        span: Option<Span>,
+    },
+    /// `position` is the offset of the byte in the input.
+    NonAsciiInput {
+        value: u8,
+        position: usize,
+        span: Option<Span>,
+    },
+    /// `.` can't write `value` in the mode chosen with [`Interpreter::with_wide_output`].
+    UnprintableValue {
+        value: u32,
+        wide_output: WideOutput,
+        span: Option<Span>,
+    },
+    /// The pointer moved past an end of the tape without [`Interpreter::with_pointer_wrap`].
+    ///
+    /// ```
+    /// use brainlift::{
+    ///     EofBehaviour, Interpreter, Parser,
+    ///     interpreter::{Direction, RuntimeError},
+    /// };
+    ///
+    /// let program = Parser::new("+\n<").with_spans(true).parse().unwrap();
+    /// let error = Interpreter::new(30_000, EofBehaviour::Ignore)
+    ///     .run(&program)
+    ///     .unwrap_err();
+    /// assert!(matches!(
+    ///     error,
+    ///     RuntimeError::PointerOutOfBounds {
+    ///         direction: Direction::Left,
+    ///         ..
+    ///     }
+    /// ));
+    /// assert_eq!(error.to_string(), "moved left out of bounds at line 2, column 1");
+    /// ```
+    PointerOutOfBounds {
+        direction: Direction,
+        span: Option<Span>,
+    },
+    /// More instructions were executed than allowed by [`Interpreter::with_max_steps`].
+    StepLimitExceeded { limit: u64, span: Option<Span> },
+    /// Reading the input or writing the output failed, e.g. because stdout was closed.
+    Io(io::Error),
+}
+
+impl From<io::Error> for RuntimeError {
+    fn from(e: io::Error) -> Self {
+        RuntimeError::Io(e)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Left,
+    Right,
+}
+
+impl RuntimeError {
+    fn out_of_bounds(direction: Direction) -> Self {
+        RuntimeError::PointerOutOfBounds {
+            direction,
+            span: None,
+        }
+    }
+
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            RuntimeError::CellValueTooLarge { span, .. }
+            | RuntimeError::NonAsciiInput { span, .. }
+            | RuntimeError::UnprintableValue { span, .. }
+            | RuntimeError::PointerOutOfBounds { span, .. }
+            | RuntimeError::StepLimitExceeded { span, .. } => *span,
+            RuntimeError::Io(_) => None,
         }
     }
 
-    fn current(&mut self) -> &mut u8 {
-        &mut self.state.array[self.state.pointer]
+    /// Sets the span if none is set yet.
+    fn with_span(mut self, position: Option<Span>) -> Self {
+        match &mut self {
+            RuntimeError::CellValueTooLarge { span, .. }
+            | RuntimeError::NonAsciiInput { span, .. }
+            | RuntimeError::UnprintableValue { span, .. }
+            | RuntimeError::PointerOutOfBounds { span, .. }
+            | RuntimeError::StepLimitExceeded { span, .. } => *span = span.or(position),
+            RuntimeError::Io(_) => {}
+        }
+        self
     }
 }
+
+impl std::fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RuntimeError::CellValueTooLarge {
+                value,
+                max,
+                position,
+                ..
+            } => write!(
+                f,
+                "value {value} written to cell {position} exceeds the maximum of {max}"
+            )?,
+            RuntimeError::NonAsciiInput {
+                value, position, ..
+            } => {
+                write!(
+                    f,
+                    "non-ASCII byte {value:#04x} at offset {position} of the input"
+                )?;
+            }
+            RuntimeError::UnprintableValue {
+                value, wide_output, ..
+            } => match wide_output {
+                WideOutput::Unicode => write!(f, "value {value} written by `.` isn't a character")?,
+                _ => write!(f, "value {value} written by `.` doesn't fit in a byte")?,
+            },
+            RuntimeError::PointerOutOfBounds { direction, .. } => {
+                let direction = match direction {
+                    Direction::Left => "left",
+                    Direction::Right => "right",
+                };
+                write!(f, "moved {direction} out of bounds")?;
+            }
+            RuntimeError::StepLimitExceeded { limit, .. } => {
+                write!(f, "exceeded the limit of {limit} steps")?;
+            }
+            RuntimeError::Io(e) => write!(f, "input or output failed: {e}")?,
+        }
+
+        if let Some(span) = self.span() {
+            write!(f, " at {span}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for RuntimeError {}
@@ -0,0 +1,37 @@
+//! The parser, interpreter and compiler behind the `brainlift` command line tool.
+//!
+//! ```
+//! use brainlift::{EofBehaviour, Interpreter, Parser};
+//!
+//! let program = Parser::new("++++++++[>++++++++<-]>+.").parse().unwrap();
+//!
+//! let mut output = Vec::new();
+//! Interpreter::new(30_000, EofBehaviour::Ignore)
+//!     .with_io(&b""[..], &mut output)
+//!     .run(&program)
+//!     .unwrap();
+//! assert_eq!(output, b"A");
+//! ```
+
+pub mod analysis;
+pub mod cli;
+pub mod compiler;
+pub mod config;
+pub mod debugger;
+pub mod expect;
+pub mod hexdump;
+pub mod inspector;
+pub mod interpreter;
+pub mod optimizer;
+pub mod parser;
+pub mod profile;
+pub mod program;
+pub mod recording;
+pub mod stats;
+pub mod transpiler;
+
+pub use cli::EofBehaviour;
+pub use compiler::Compiler;
+pub use interpreter::Interpreter;
+pub use parser::Parser;
+pub use program::{Instruction, Program};
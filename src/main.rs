@@ -1,41 +1,712 @@
-use std::fs;
-
-pub mod cli;
-pub mod compiler;
-pub mod interpreter;
-pub mod parser;
-pub mod program;
-
-use clap::Parser as _;
-use parser::Parser;
+use std::{
+    env,
+    fs::{self, OpenOptions},
+    io::{self, BufRead, BufReader, BufWriter, Read, Write},
+    path::Path,
+    process,
+    time::Instant,
+};
 
-use crate::{
-    cli::Commands::{Compile, Run},
-    compiler::Compiler,
-    interpreter::Interpreter,
+use brainlift::{
+    analysis,
+    cli::{
+        Args, CellSize, CodegenOpt,
+        Commands::{
+            Analyze, Check, Compile, Diff, Inspect, Minify, Profile, Run, Stats, Targets, Test,
+            Transpile,
+        },
+        Emit, ListFormat, OutputFormat, OutputTarget, Pass, TapeStorage,
+    },
+    compiler::{self, CompileError, Compiler},
+    config::Config,
+    debugger,
+    expect::ExpectedOutput,
+    hexdump::HexDump,
+    inspector,
+    interpreter::{Cell, Interpreter, RunStatus, RuntimeError},
+    optimizer,
+    parser::{self, Parser},
+    program::{FlatProgram, Program},
+    recording::{self, Recorder, Recording},
+    stats,
+    transpiler::Transpiler,
 };
+use clap::{CommandFactory, error::ErrorKind};
+
+/// Evaluates `$body` with `$cell` as an alias for the cell type of `$cell_size`.
+macro_rules! with_cell_type {
+    ($cell_size:expr, $cell:ident => $body:expr) => {
+        match $cell_size {
+            CellSize::Bits8 => {
+                type $cell = u8;
+                $body
+            }
+            CellSize::Bits16 => {
+                type $cell = u16;
+                $body
+            }
+            CellSize::Bits32 => {
+                type $cell = u32;
+                $body
+            }
+        }
+    };
+}
 
 fn main() {
-    let args = cli::Args::parse();
+    let mut args = Args::parse_with_defaults();
 
-    let content = match &args.command {
-        Run { input } => fs::read_to_string(input),
-        Compile { input, output: _ } => fs::read_to_string(input),
+    if args.print_config {
+        print!("{}", Config::resolved(&args));
+        return;
     }
-    .expect("failed to read input file");
 
-    let mut parser = Parser::new(&content);
+    let Some(command) = args.command.take() else {
+        Args::command()
+            .error(ErrorKind::MissingSubcommand, "a subcommand is required")
+            .exit();
+    };
+
+    match command {
+        Run {
+            input,
+            value_histogram,
+            deterministic_input,
+            trim_output,
+            flat,
+            output,
+            output_format,
+            output_buffer,
+            max_cell_value,
+            strict_ascii_input,
+            input_mode,
+            input_file,
+            input_string,
+            record,
+            replay,
+            exit_with_cell,
+            debug,
+            debug_window,
+        } => {
+            // these look at the cells, which evaluating the program ahead of time leaves out
+            if max_cell_value.is_some() || value_histogram || exit_with_cell {
+                args.static_eval = false;
+                if let Some(passes) = &mut args.opt_passes {
+                    passes.retain(|&pass| pass != Pass::StaticEval);
+                }
+            }
+            let source = read_source(&input);
+            let parse_start = Instant::now();
+            let program = parse_program(&args, &input, &source);
+            let parse_time = parse_start.elapsed();
+            let program_input: Option<Box<dyn io::Read>> = match (input_file, input_string) {
+                (Some(path), _) => Some(Box::new(fs::File::open(&path).unwrap_or_else(|e| {
+                    eprintln!("error: failed to open {path:?}: {e}");
+                    process::exit(1);
+                }))),
+                (None, Some(text)) => Some(Box::new(io::Cursor::new(text.into_bytes()))),
+                (None, None) => embedded_input(&program),
+            };
+            let replay =
+                replay.map(|replay| load_recording(&replay, &source, &input, args.cell_size));
+            let settings = match &replay {
+                Some(recording) => recording.settings,
+                None => recording::Settings {
+                    array_size: args.array_size(&program),
+                    eof_behaviour: args.eof_behaviour,
+                    cell_size: args.cell_size,
+                    pointer_wrap: args.pointer_wrap,
+                    signed_tape: args.signed_tape,
+                    fixed_tape: args.fixed_tape,
+                    input_mode,
+                    max_steps: args.max_steps,
+                    strict_ascii_input,
+                    max_cell_value,
+                },
+            };
+
+            with_cell_type!(args.cell_size, C => {
+                // a maximum the cells can't reach is never exceeded
+                let max_cell_value = settings
+                    .max_cell_value
+                    .map_or(C::MAX, |max| C::try_from(max).unwrap_or(C::MAX));
 
-    let program = parser.parse().expect("failed to parse program");
+                let mut interpreter =
+                    Interpreter::<C>::new_with_cells(settings.array_size, settings.eof_behaviour)
+                    .with_value_histogram(value_histogram)
+                    .with_deterministic_input(deterministic_input)
+                    .with_trim_output(trim_output)
+                    .with_flat(flat)
+                    .with_debug_window(debug_window)
+                    .with_pointer_wrap(settings.pointer_wrap)
+                    .with_signed_tape(settings.signed_tape)
+                    .with_wide_output(args.wide_output)
+                    .with_fixed_tape(settings.fixed_tape)
+                    .with_max_steps(settings.max_steps)
+                    .with_max_cell_value(max_cell_value)
+                    .with_strict_ascii_input(settings.strict_ascii_input)
+                    .with_input_mode(settings.input_mode);
+                let stdout = BufWriter::with_capacity(output_buffer, io::stdout());
+                interpreter = match (output, output_format) {
+                    (OutputTarget::Stdout, OutputFormat::Raw) => interpreter.with_output(stdout),
+                    (OutputTarget::Stdout, OutputFormat::Hex) => {
+                        interpreter.with_finishing_output(HexDump::new(stdout))
+                    }
+                    (OutputTarget::Null, _) => interpreter.with_output(io::sink()),
+                };
 
-    match args.command {
-        Run { input: _ } => {
-            let mut interpreter = Interpreter::new(args.array_size as usize, args.eof_behaviour);
-            interpreter.run(&program);
+                if let Some(recording) = replay {
+                    interpreter = interpreter.with_input(io::Cursor::new(recording.input));
+                } else if let Some(record) = record {
+                    let recorder = Recorder::create(
+                        &record,
+                        program_input.unwrap_or_else(|| Box::new(io::stdin())),
+                        &source,
+                        &settings,
+                    )
+                    .expect("failed to create recording");
+                    interpreter = interpreter.with_input(recorder);
+                } else if let Some(program_input) = program_input {
+                    interpreter = interpreter.with_input(program_input);
+                }
+
+                let run_start = Instant::now();
+                let result = if debug {
+                    run_debugger(&mut interpreter, &program)
+                } else {
+                    interpreter.run(&program)
+                };
+                if args.time {
+                    eprintln!(
+                        "parsed in {parse_time:.2?}, ran in {:.2?}",
+                        run_start.elapsed()
+                    );
+                }
+                exit_on_runtime_error(result);
+                if exit_with_cell {
+                    process::exit(interpreter.current_cell().low_byte().into());
+                }
+            })
+        }
+        Compile {
+            input,
+            output,
+            emit,
+            codegen_opt,
+            entry,
+            size_report,
+            list_symbols,
+            check_output,
+            check_bounds,
+            interruptible,
+            no_free,
+            call_conv,
+            chunk_size,
+            cache_dir,
+            emit_callgraph,
+            tape_storage,
+            pie,
+            no_pie,
+            cell_endian,
+        } => {
+            let target = args.target.clone();
+            if emit == Emit::Exe && target != env::consts::ARCH {
+                Args::command()
+                    .error(
+                        ErrorKind::ArgumentConflict,
+                        format!("`--target {target}` can only be compiled with `--emit obj`"),
+                    )
+                    .exit();
+            }
+            if emit != Emit::Exe && (pie || no_pie) {
+                let flag = if pie { "--pie" } else { "--no-pie" };
+                Args::command()
+                    .error(
+                        ErrorKind::ArgumentConflict,
+                        format!("`{flag}` only applies to executables"),
+                    )
+                    .exit();
+            }
+            if emit == Emit::Exe && entry != compiler::DEFAULT_ENTRY {
+                Args::command()
+                    .error(
+                        ErrorKind::ArgumentConflict,
+                        format!("`--entry {entry}` can only be compiled with `--emit obj`"),
+                    )
+                    .exit();
+            }
+            let output = output.unwrap_or_else(|| {
+                let output = match emit {
+                    Emit::Obj => input.with_extension("o"),
+                    Emit::Exe => input.with_extension(""),
+                    Emit::Clif => input.with_extension("clif"),
+                };
+                let reason = if input == Path::new("-") {
+                    "when reading the program from stdin".to_owned()
+                } else if output == input {
+                    format!("as the default output would overwrite {input:?}")
+                } else {
+                    return output;
+                };
+                Args::command()
+                    .error(
+                        ErrorKind::MissingRequiredArgument,
+                        format!("`-o` is required {reason}"),
+                    )
+                    .exit();
+            });
+            let program = load_program(&args, &input);
+            if tape_storage == TapeStorage::Stack
+                && args.array_size(&program) * args.cell_size.bytes()
+                    > compiler::MAX_STACK_TAPE_SIZE
+            {
+                Args::command()
+                    .error(
+                        ErrorKind::ValueValidation,
+                        format!(
+                            "a stack tape can hold at most {} bytes",
+                            compiler::MAX_STACK_TAPE_SIZE
+                        ),
+                    )
+                    .exit();
+            }
+
+            let compiler = Compiler::new(args.array_size(&program), args.eof_behaviour)
+                .with_target(target)
+                .with_entry(entry)
+                .with_codegen_opt(codegen_opt.unwrap_or(CodegenOpt::for_level(args.optimize)))
+                .with_size_report(size_report)
+                .with_list_symbols(list_symbols)
+                .with_check_output(check_output)
+                .with_check_bounds(check_bounds)
+                .with_interruptible(interruptible)
+                .with_free_tape(!no_free)
+                .with_call_conv(call_conv)
+                .with_chunk_size(chunk_size.map(|size| size as usize))
+                .with_cache_dir(cache_dir)
+                .with_callgraph(emit_callgraph)
+                .with_tape_storage(tape_storage)
+                .with_cell_size(args.cell_size)
+                .with_cell_endian(cell_endian)
+                .with_wide_output(args.wide_output)
+                .with_pointer_wrap(args.pointer_wrap)
+                .with_signed_tape(args.signed_tape)
+                .with_emit(emit)
+                .with_pie((pie || no_pie).then_some(pie))
+                .with_verbose(args.verbose);
+            if let Err(e) = compiler.compile(&program, output) {
+                eprintln!("error: {e}");
+                if let CompileError::Verification(_) = e {
+                    eprintln!(
+                        "this is a bug in brainlift, please report it along with the program"
+                    );
+                }
+                process::exit(1);
+            }
+        }
+        Diff {
+            first,
+            second,
+            input,
+            compare_tape,
+        } => {
+            let first = load_program(&args, &first);
+            let second = load_program(&args, &second);
+            let input = input.unwrap_or_default();
+
+            let same = with_cell_type!(args.cell_size, C => {
+                diff::<C>(&args, &first, &second, input.as_bytes(), compare_tape)
+            });
+            if !same {
+                process::exit(1);
+            }
+        }
+        Analyze {
+            input,
+            effects: _,
+            format,
+        } => {
+            let program = load_program(&args, &input);
+            analysis::print_effects(&analysis::block_effects(&program), format);
+        }
+        Inspect { input } => {
+            if args.cell_size != CellSize::Bits8 {
+                Args::command()
+                    .error(
+                        ErrorKind::ArgumentConflict,
+                        "inspect only supports 8-bit cells",
+                    )
+                    .exit();
+            }
+
+            let program = load_program(&args, &input);
+            let mut interpreter = Interpreter::new(args.array_size(&program), args.eof_behaviour)
+                .with_pointer_wrap(args.pointer_wrap)
+                .with_signed_tape(args.signed_tape)
+                .with_wide_output(args.wide_output)
+                .with_fixed_tape(args.fixed_tape)
+                .with_max_steps(args.max_steps);
+            if let Some(input) = embedded_input(&program) {
+                interpreter = interpreter.with_input(input);
+            }
+            let title = match interpreter.run_to_breakpoint(&FlatProgram::from(&program)) {
+                Ok(RunStatus::Yielded) => format!("{} stopped at `#`", input.display()),
+                Ok(RunStatus::Finished) => format!("{} finished", input.display()),
+                Ok(RunStatus::WaitingForInput) => unreachable!("only run_for waits for input"),
+                Err(e) => format!("{} stopped: {e}", input.display()),
+            };
+
+            // show at least as far as the pointer, even if those cells are zero
+            let mut tape = interpreter.tape().to_vec();
+            tape.resize(tape.len().max(interpreter.pointer() + 1), 0);
+
+            if let Err(e) = inspector::inspect(&tape, interpreter.pointer(), &title) {
+                eprintln!("error: failed to show the tape on the terminal: {e}");
+                process::exit(1);
+            }
+        }
+        Stats {
+            path,
+            recursive,
+            format,
+        } => {
+            let results = stats::collect(&path, recursive).expect("failed to read directory");
+            stats::print(&results, format);
         }
-        Compile { input, output } => {
-            let compiler = Compiler::new(args.array_size as usize, args.eof_behaviour);
-            compiler.compile(&program, output.unwrap_or(input.with_extension("o")));
+        Targets { format } => {
+            let targets = compiler::supported_targets();
+            match format {
+                ListFormat::Text => targets.iter().for_each(|target| println!("{target}")),
+                ListFormat::Json => {
+                    let targets: Vec<_> = targets.iter().map(|t| format!("\"{t}\"")).collect();
+                    println!("[{}]", targets.join(","));
+                }
+            }
         }
+        Test {
+            program,
+            expect_file,
+            input,
+        } => {
+            let program = load_program(&args, &program);
+            let expected = fs::File::open(&expect_file).unwrap_or_else(|e| {
+                eprintln!("error: failed to open {expect_file:?}: {e}");
+                process::exit(1);
+            });
+            let failed_to_read = |e: io::Error| -> ! {
+                eprintln!("error: failed to read {expect_file:?}: {e}");
+                process::exit(1);
+            };
+            let mut output = ExpectedOutput::new(BufReader::new(expected));
+            let input = input
+                .map(String::into_bytes)
+                .or_else(|| program.input.clone())
+                .unwrap_or_default();
+
+            // the input is in memory, so I/O errors come from reading the expected output
+            match run_with_io(&args, &program, &input[..], &mut output) {
+                Err(RuntimeError::Io(e)) => failed_to_read(e),
+                result => exit_on_runtime_error(result),
+            }
+
+            match output.finish().unwrap_or_else(|e| failed_to_read(e)) {
+                Some(mismatch) => {
+                    println!("{mismatch}");
+                    process::exit(1);
+                }
+                None => println!("output matches"),
+            }
+        }
+        Check {
+            input,
+            quine,
+            quiet,
+        } => {
+            let source = read_source(&input);
+            if !quine {
+                let program = parse_source(&args, &input, &source, quiet);
+                if !quiet {
+                    let instructions = stats::ProgramStats::of(&program).instructions;
+                    println!("ok, {instructions} instructions");
+                }
+                return;
+            }
+
+            let program = parse_program(&args, &input, &source);
+
+            let expected = parser::minify(&source);
+            let mut output = ExpectedOutput::new(&expected[..]);
+            exit_on_runtime_error(run_with_io(&args, &program, io::empty(), &mut output));
+
+            // like `test`, with the source as the expected output
+            if let Some(mismatch) = output.finish().expect("the source is in memory") {
+                println!("{mismatch}");
+                process::exit(1);
+            }
+            println!("program is a quine");
+        }
+        Profile { input } => {
+            let program = load_program(&args, &input);
+            with_cell_type!(args.cell_size, C => {
+                let mut interpreter =
+                    Interpreter::<C>::new_with_cells(args.array_size(&program), args.eof_behaviour)
+                        .with_pointer_wrap(args.pointer_wrap)
+                        .with_signed_tape(args.signed_tape)
+                        .with_wide_output(args.wide_output)
+                        .with_fixed_tape(args.fixed_tape)
+                        .with_max_steps(args.max_steps)
+                        .with_profile(true);
+                if let Some(input) = embedded_input(&program) {
+                    interpreter = interpreter.with_input(input);
+                }
+                exit_on_runtime_error(interpreter.run(&program));
+
+                let profile = interpreter.profile().expect("profiling is enabled");
+                profile.print(&program);
+            })
+        }
+        Minify { input, output } => {
+            let program = parse_source(&args, &input, &read_source(&input), false);
+
+            let mut minified = String::new();
+            if let Some(tape_size) = program.tape_size {
+                // the directive has to stay alone on the first line
+                minified.push_str(&format!(";tape {tape_size}\n"));
+            }
+            minified.push_str(&program.to_string());
+            if let Some(input) = &program.input {
+                minified.push('!');
+                minified.push_str(&String::from_utf8_lossy(input));
+            }
+            match output {
+                Some(path) => fs::write(path, minified).expect("failed to write output file"),
+                None => print!("{minified}"),
+            }
+        }
+        Transpile {
+            input,
+            output,
+            annotate,
+        } => {
+            let program = load_program(&args, &input);
+            let out: Box<dyn io::Write> = match output {
+                Some(path) => {
+                    Box::new(fs::File::create(path).expect("failed to create output file"))
+                }
+                None => Box::new(io::stdout().lock()),
+            };
+
+            Transpiler::new(
+                BufWriter::new(out),
+                args.array_size(&program),
+                args.eof_behaviour,
+            )
+            .with_cell_size(args.cell_size)
+            .with_wide_output(args.wide_output)
+            .with_pointer_wrap(args.pointer_wrap)
+            .with_signed_tape(args.signed_tape)
+            .with_annotate(annotate)
+            .transpile(&program)
+            .expect("failed to write C source");
+        }
+    }
+}
+
+/// Runs `program` in the debugger, which uses the controlling terminal if there is one, as stdin
+/// and stdout may be redirected for the program. Otherwise it falls back to stdin and stderr.
+fn run_debugger<C: Cell>(
+    interpreter: &mut Interpreter<'_, C>,
+    program: &Program,
+) -> Result<(), RuntimeError> {
+    let program = FlatProgram::from(program);
+    match OpenOptions::new().read(true).write(true).open("/dev/tty") {
+        Ok(tty) => {
+            let mut commands = BufReader::new(tty.try_clone()?);
+            debugger::debug(interpreter, &program, |line| commands.read_line(line), tty)
+        }
+        // stdin is locked for each command only, as `,` reads from it too
+        Err(_) => debugger::debug(
+            interpreter,
+            &program,
+            |line| io::stdin().read_line(line),
+            io::stderr(),
+        ),
+    }
+}
+
+fn exit_on_runtime_error<T>(result: Result<T, RuntimeError>) -> T {
+    result.unwrap_or_else(|e| {
+        eprintln!("error: {e}");
+        process::exit(1);
+    })
+}
+
+/// Loads a recording to replay, exiting if it can't be read or was made with another program or
+/// cell size.
+fn load_recording(path: &Path, source: &str, input: &Path, cell_size: CellSize) -> Recording {
+    let recording = Recording::load(path).unwrap_or_else(|e| {
+        eprintln!("error: failed to load {path:?}: {e}");
+        process::exit(1);
+    });
+
+    if !recording.matches_source(source) {
+        eprintln!("error: {path:?} was recorded with a different program than {input:?}");
+        process::exit(1);
+    }
+    // the cell size decides which optimizations the program was loaded with, so it can't simply
+    // be taken from the recording
+    if recording.settings.cell_size != cell_size {
+        let bits = recording.settings.cell_size.bytes() * 8;
+        eprintln!("error: {path:?} was recorded with `--cell-size {bits}`");
+        process::exit(1);
+    }
+
+    recording
+}
+
+/// Reads the source of the program at `input`, or from stdin if it is `-`. Stdin is read to its
+/// end before the program runs, so `,` then only sees EOF.
+fn read_source(input: &Path) -> String {
+    if input == Path::new("-") {
+        let mut source = String::new();
+        io::stdin()
+            .read_to_string(&mut source)
+            .expect("failed to read program from stdin");
+        source
+    } else {
+        fs::read_to_string(input).expect("failed to read input file")
     }
 }
+
+fn load_program(args: &Args, input: &Path) -> Program {
+    parse_program(args, input, &read_source(input))
+}
+
+/// Parses the `source` read from `input` with the parser settings of `args`, and exits with
+/// status 1 if it isn't a valid program. The error is printed unless `quiet` is set.
+fn parse_source(args: &Args, input: &Path, source: &str, quiet: bool) -> Program {
+    Parser::new(source)
+        .with_max_instructions(args.max_instructions)
+        .with_embedded_input(args.embedded_input)
+        .with_spans(true)
+        .parse()
+        .unwrap_or_else(|e| {
+            if !quiet {
+                eprintln!("error: failed to parse {input:?}: {e}");
+            }
+            process::exit(1);
+        })
+}
+
+/// Parses the `source` read from `input` and applies the enabled optimization passes.
+fn parse_program(args: &Args, input: &Path, source: &str) -> Program {
+    let mut program = parse_source(args, input, source, false);
+
+    let mut passes = args.passes();
+    // the evaluation runs the program without the step limit
+    if args.max_steps.is_some() {
+        passes.retain(|&pass| pass != Pass::StaticEval);
+    }
+    let array_size = args.array_size(&program);
+    optimizer::run_passes(&mut program, &passes, array_size);
+
+    if args.dump_ir {
+        eprint!("{}", program.tree());
+    }
+
+    if args.dump_bytecode {
+        print!("{}", FlatProgram::from(&program).listing());
+        process::exit(0);
+    }
+
+    program
+}
+
+/// The input that followed a `!` in the program's source, for commands that would otherwise
+/// read `,` from stdin.
+fn embedded_input(program: &Program) -> Option<Box<dyn Read>> {
+    let input = program.input.clone()?;
+    Some(Box::new(io::Cursor::new(input)))
+}
+
+/// Runs `program` with the configured tape, reading `,` from `input` and writing `.` to `output`.
+fn run_with_io(
+    args: &Args,
+    program: &Program,
+    input: impl Read,
+    output: impl Write,
+) -> Result<(), RuntimeError> {
+    with_cell_type!(args.cell_size, C => {
+        Interpreter::<C>::new_with_cells(args.array_size(program), args.eof_behaviour)
+            .with_pointer_wrap(args.pointer_wrap)
+            .with_signed_tape(args.signed_tape)
+            .with_wide_output(args.wide_output)
+            .with_fixed_tape(args.fixed_tape)
+            .with_max_steps(args.max_steps)
+            .with_io(input, output)
+            .run(program)
+    })
+}
+
+/// Runs both programs on the same input and reports the first difference in their output (and
+/// optionally their final tapes). Returns whether they behaved the same.
+fn diff<C: Cell>(
+    args: &Args,
+    first: &Program,
+    second: &Program,
+    input: &[u8],
+    compare_tape: bool,
+) -> bool {
+    let run = |program: &Program| {
+        let mut output = Vec::new();
+        let tape = {
+            let mut interpreter =
+                Interpreter::<C>::new_with_cells(args.array_size(program), args.eof_behaviour)
+                    .with_pointer_wrap(args.pointer_wrap)
+                    .with_signed_tape(args.signed_tape)
+                    .with_wide_output(args.wide_output)
+                    .with_fixed_tape(args.fixed_tape)
+                    .with_max_steps(args.max_steps)
+                    .with_io(input, &mut output);
+            exit_on_runtime_error(interpreter.run(program));
+            interpreter.tape().to_vec()
+        };
+        (output, tape)
+    };
+
+    let (first_output, first_tape) = run(first);
+    let (second_output, second_tape) = run(second);
+
+    let mut same = report_difference("output", "byte", &first_output, &second_output);
+    if compare_tape {
+        same &= report_difference("tape", "cell", &first_tape, &second_tape);
+    }
+
+    if same {
+        println!("programs behave the same");
+    }
+    same
+}
+
+/// Reports the first `unit` (e.g. byte) at which `first` and `second` differ, if any. Returns
+/// whether they are the same.
+fn report_difference<T: Cell>(what: &str, unit: &str, first: &[T], second: &[T]) -> bool {
+    let Some(offset) = (0..first.len().max(second.len())).find(|&i| first.get(i) != second.get(i))
+    else {
+        return true;
+    };
+
+    let describe = |values: &[T]| match values.get(offset).map(|&value| value.into()) {
+        Some(value) => match u8::try_from(value) {
+            Ok(byte) => format!("{value:#04x} {:?}", byte as char),
+            Err(_) => format!("{value:#04x}"),
+        },
+        None => "end".to_string(),
+    };
+    println!(
+        "{what} differs at {unit} {offset}: {} vs. {}",
+        describe(first),
+        describe(second)
+    );
+    false
+}
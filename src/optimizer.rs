@@ -0,0 +1,727 @@
+use std::{collections::BTreeMap, ops::Range};
+
+use crate::{
+    cli::Pass,
+    program::{Instruction, Program, Span},
+};
+
+/// Upper bound on the number of instructions executed while evaluating a program ahead of time.
+const STATIC_EVALUATION_STEP_LIMIT: u64 = 100_000_000;
+
+/// Runs the `passes` over `program` one after another, e.g. those given by `--opt-passes`.
+/// `max_array_size` bounds the tape for [`Pass::Init`] and [`Pass::StaticEval`].
+///
+/// ```
+/// use brainlift::{Program, cli::Pass, optimizer};
+///
+/// let mut program = Program::parse("[-]++[>>>-<<<-]").unwrap();
+/// optimizer::run_passes(&mut program, &[Pass::Clears, Pass::Add, Pass::Moves], 10);
+/// assert_eq!(program.tree(), "set zero\nadd 2\nloop\n  move 3\n  -\n  move -3\n  -\n");
+///
+/// // `set` only folds additions into clears found by an earlier `clears`
+/// let mut program = Program::parse("[-]++").unwrap();
+/// optimizer::run_passes(&mut program, &[Pass::Add, Pass::Set, Pass::Clears], 10);
+/// assert_eq!(program.tree(), "set zero\nadd 2\n");
+/// optimizer::run_passes(&mut program, &[Pass::Set], 10);
+/// assert_eq!(program.tree(), "set 2\n");
+/// ```
+pub fn run_passes(program: &mut Program, passes: &[Pass], max_array_size: usize) {
+    for pass in passes {
+        match pass {
+            Pass::Add => fold_additions(program),
+            Pass::Moves => fold_moves(program),
+            Pass::Clears => fold_clear_loops(program),
+            Pass::Multiply => fold_multiply_loops(program),
+            Pass::Set => fold_set_constants(program),
+            Pass::Scans => fold_scan_loops(program),
+            Pass::Init => fold_initialization(program, max_array_size),
+            Pass::StaticEval => {
+                if let Some(evaluated) = evaluate_statically(program, max_array_size) {
+                    *program = evaluated;
+                }
+            }
+        }
+    }
+}
+
+/// Runs a program that never reads input ahead of time and replaces it with a single
+/// [`Instruction::OutputBytes`] holding everything it writes.
+///
+/// Returns `None` if the program executes `,` or `#`, moves the pointer out of bounds, or doesn't
+/// finish within [`STATIC_EVALUATION_STEP_LIMIT`] steps. The program is then left to be executed
+/// at runtime as usual, as is a program with a noopt region. The evaluation assumes 8-bit cells.
+/// The result has no source positions, as writing the bytes can't fail. Limits on the run like
+/// `--max-steps` aren't applied, so callers skip this pass when the run is limited.
+///
+/// ```
+/// use brainlift::{Program, optimizer};
+///
+/// // without the `#` breakpoints, which stop the evaluation
+/// let source = std::fs::read_to_string("examples/helloworld.b").unwrap().replace('#', "");
+/// let evaluated = optimizer::evaluate_statically(&Program::parse(&source).unwrap(), 30_000);
+/// assert_eq!(evaluated.unwrap().tree(), "output bytes \"Hello World!\\n\"\n");
+///
+/// // the input isn't known ahead of time
+/// let program = Program::parse("+[,.]").unwrap();
+/// assert!(optimizer::evaluate_statically(&program, 30_000).is_none());
+/// ```
+pub fn evaluate_statically(program: &Program, max_array_size: usize) -> Option<Program> {
+    if program.noopt.is_some() {
+        return None;
+    }
+    let mut evaluator = StaticEvaluator {
+        max_array_size,
+        array: vec![0; 1],
+        pointer: 0,
+        steps: 0,
+        output: Vec::new(),
+    };
+    evaluator.evaluate(&program.instructions)?;
+
+    Some(Program {
+        instructions: vec![Instruction::OutputBytes(evaluator.output)],
+        tape_size: program.tape_size,
+        spans: None,
+        noopt: None,
+        input: program.input.clone(),
+    })
+}
+
+/// Replaces the `+`, `-`, `>` and `<` at the very start of a program, where the tape is known to
+/// be zero, with a single [`Instruction::InitCells`] holding the resulting cell values, followed
+/// by a single move to the final pointer position. Large initializers can then be applied as one
+/// copy instead of many individual stores.
+///
+/// The block ends at the first loop, I/O or noopt region. It is left untouched if it moves the
+/// pointer out of bounds or doesn't change any cell. The cell values are computed for 8-bit cells.
+///
+/// ```
+/// use brainlift::{Program, optimizer};
+///
+/// let mut program = Program::parse(">++>+++>>.").unwrap();
+/// optimizer::fold_initialization(&mut program, 10);
+/// assert_eq!(program.tree(), "init cells from 1 to [2, 3]\nmove 4\n.\n");
+///
+/// // back at the start, no move is needed
+/// let mut program = Program::parse("+>-<[-]").unwrap();
+/// optimizer::fold_initialization(&mut program, 10);
+/// assert_eq!(program.tree(), "init cells from 0 to [1, 255]\nloop\n  -\n");
+/// ```
+pub fn fold_initialization(program: &mut Program, max_array_size: usize) {
+    let noopt = program.noopt.as_deref().unwrap_or_default();
+    let block_len = program
+        .instructions
+        .iter()
+        .enumerate()
+        .position(|(index, instruction)| {
+            // the block has no loops, so the index is also the one in source order
+            noopt.get(index) == Some(&true)
+                || !matches!(
+                    instruction,
+                    Instruction::Increment
+                        | Instruction::Decrement
+                        | Instruction::Add(_)
+                        | Instruction::Right
+                        | Instruction::Left
+                        | Instruction::Move(_)
+                        | Instruction::SetZero
+                        | Instruction::Set(_)
+                )
+        })
+        .unwrap_or(program.instructions.len());
+
+    let mut cells = vec![0u8; 1];
+    let mut pointer = 0usize;
+    for instruction in &program.instructions[..block_len] {
+        match instruction {
+            Instruction::Increment => cells[pointer] = cells[pointer].wrapping_add(1),
+            Instruction::Decrement => cells[pointer] = cells[pointer].wrapping_sub(1),
+            Instruction::Add(n) => cells[pointer] = cells[pointer].wrapping_add_signed(*n),
+            Instruction::Right => {
+                pointer += 1;
+                if pointer >= max_array_size {
+                    return;
+                }
+                if pointer == cells.len() {
+                    cells.push(0);
+                }
+            }
+            Instruction::Left => {
+                let Some(left) = pointer.checked_sub(1) else {
+                    return;
+                };
+                pointer = left;
+            }
+            Instruction::Move(offset) => {
+                let Some(moved) = pointer
+                    .checked_add_signed(*offset)
+                    .filter(|&moved| moved < max_array_size)
+                else {
+                    return;
+                };
+                pointer = moved;
+                if pointer >= cells.len() {
+                    cells.resize(pointer + 1, 0);
+                }
+            }
+            Instruction::SetZero => cells[pointer] = 0,
+            Instruction::Set(value) => cells[pointer] = *value,
+            _ => unreachable!("the block only contains cell updates and moves"),
+        }
+    }
+
+    // zero cells at either end don't need to be written, as the tape is zero already
+    let Some(first) = cells.iter().position(|&cell| cell != 0) else {
+        return;
+    };
+    let last = cells.iter().rposition(|&cell| cell != 0).unwrap_or(first);
+
+    let init = Instruction::InitCells {
+        base_offset: first,
+        values: cells[first..=last].to_vec(),
+    };
+    let moved = (pointer != 0).then_some(Instruction::Move(pointer as isize));
+    if let Some(spans) = &mut program.spans {
+        let first = spans[0];
+        let last = moved.is_some().then_some(spans[block_len - 1]);
+        spans.splice(..block_len, std::iter::once(first).chain(last));
+    }
+    if let Some(noopt) = &mut program.noopt {
+        let replacements = 1 + usize::from(moved.is_some());
+        noopt.splice(..block_len, std::iter::repeat_n(false, replacements));
+    }
+    program
+        .instructions
+        .splice(..block_len, std::iter::once(init).chain(moved));
+}
+
+/// Replaces every run of two or more `+` and `-` with a single [`Instruction::Add`] of their net
+/// count, wrapping around like an 8-bit cell does. Runs that cancel out are removed entirely.
+///
+/// ```
+/// use brainlift::{Program, optimizer};
+///
+/// let mut program = Program::parse("+++-.+-.[--].+").unwrap();
+/// optimizer::fold_additions(&mut program);
+/// assert_eq!(program.tree(), "add 2\n.\n.\nloop\n  add -2\n.\n+\n");
+/// ```
+pub fn fold_additions(program: &mut Program) {
+    let mut info = SourceInfo::take(program);
+    fold_runs(
+        &mut program.instructions,
+        &mut info,
+        |instruction| {
+            matches!(
+                instruction,
+                Instruction::Increment | Instruction::Decrement | Instruction::Add(_)
+            )
+        },
+        |run| {
+            let net = run.iter().fold(0u8, |net, instruction| match instruction {
+                Instruction::Increment => net.wrapping_add(1),
+                Instruction::Decrement => net.wrapping_sub(1),
+                Instruction::Add(n) => net.wrapping_add_signed(*n),
+                _ => unreachable!("runs only contain additions"),
+            });
+            (net != 0).then_some(Instruction::Add(net as i8))
+        },
+    );
+    info.finish(program);
+}
+
+/// Replaces every run of two or more `>` and `<` with a single [`Instruction::Move`] of their
+/// net offset. Runs that cancel out are removed entirely.
+///
+/// ```
+/// use brainlift::{Program, optimizer};
+///
+/// let mut program = Program::parse(">>>.><.[<<]>").unwrap();
+/// optimizer::fold_moves(&mut program);
+/// assert_eq!(program.tree(), "move 3\n.\n.\nloop\n  move -2\n>\n");
+/// ```
+///
+/// The folded move keeps the source position of the first move it replaces:
+///
+/// ```
+/// use brainlift::{EofBehaviour, Interpreter, Parser, optimizer};
+///
+/// let mut program = Parser::new("+\n>>\n<<<").with_spans(true).parse().unwrap();
+/// optimizer::fold_moves(&mut program);
+/// let error = Interpreter::new(10, EofBehaviour::Ignore).run(&program).unwrap_err();
+/// assert_eq!(error.to_string(), "moved left out of bounds at line 2, column 1");
+/// ```
+pub fn fold_moves(program: &mut Program) {
+    let mut info = SourceInfo::take(program);
+    fold_runs(
+        &mut program.instructions,
+        &mut info,
+        |instruction| {
+            matches!(
+                instruction,
+                Instruction::Right | Instruction::Left | Instruction::Move(_)
+            )
+        },
+        |run| {
+            let net: isize = run
+                .iter()
+                .map(|instruction| match instruction {
+                    Instruction::Right => 1,
+                    Instruction::Left => -1,
+                    Instruction::Move(offset) => *offset,
+                    _ => unreachable!("runs only contain moves"),
+                })
+                .sum();
+            (net != 0).then_some(Instruction::Move(net))
+        },
+    );
+    info.finish(program);
+}
+
+/// Replaces every loop whose body is a single `+` or `-`, which always ends with the cell at
+/// zero, with an [`Instruction::SetZero`].
+///
+/// ```
+/// use brainlift::{Program, optimizer};
+///
+/// let mut program = Program::parse("[-]>[+]>[->+<]>[[-]]").unwrap();
+/// optimizer::fold_clear_loops(&mut program);
+/// assert_eq!(
+///     program.tree(),
+///     "set zero\n>\nset zero\n>\nloop\n  -\n  >\n  +\n  <\n>\nloop\n  set zero\n"
+/// );
+/// ```
+///
+/// Like every pass, it leaves the instructions in a noopt region as they are:
+///
+/// ```
+/// use brainlift::{Program, optimizer};
+///
+/// let mut program = Program::parse("[-]\n; noopt-begin\n[-]\n; noopt-end\n[-]").unwrap();
+/// optimizer::fold_clear_loops(&mut program);
+/// assert_eq!(program.tree(), "set zero\nloop\n  -\nset zero\n");
+/// ```
+pub fn fold_clear_loops(program: &mut Program) {
+    let mut info = SourceInfo::take(program);
+    fold_clear_loops_in(&mut program.instructions, &mut info);
+    info.finish(program);
+}
+
+fn fold_clear_loops_in(instructions: &mut [Instruction], info: &mut SourceInfo) {
+    for instruction in instructions {
+        let Instruction::Loop(body) = instruction else {
+            info.keep();
+            continue;
+        };
+        if let [Instruction::Increment | Instruction::Decrement] = body.as_slice()
+            && info.is_optimizable(0..2)
+        {
+            *instruction = Instruction::SetZero;
+            info.fold(2);
+        } else {
+            info.keep();
+            fold_clear_loops_in(body, info);
+        }
+    }
+}
+
+/// Replaces every loop whose body only moves the pointer, like `[>]` or `[<<]`, with an
+/// [`Instruction::Scan`] for the next zero cell in that direction.
+///
+/// ```
+/// use brainlift::{Program, optimizer};
+///
+/// let mut program = Program::parse("[>][<][>>>][>+]").unwrap();
+/// optimizer::fold_moves(&mut program);
+/// optimizer::fold_scan_loops(&mut program);
+/// assert_eq!(
+///     program.tree(),
+///     "scan by 1\nscan by -1\nscan by 3\nloop\n  >\n  +\n"
+/// );
+/// ```
+pub fn fold_scan_loops(program: &mut Program) {
+    let mut info = SourceInfo::take(program);
+    fold_scan_loops_in(&mut program.instructions, &mut info);
+    info.finish(program);
+}
+
+fn fold_scan_loops_in(instructions: &mut [Instruction], info: &mut SourceInfo) {
+    for instruction in instructions {
+        let Instruction::Loop(body) = instruction else {
+            info.keep();
+            continue;
+        };
+        let step = match body.as_slice() {
+            [Instruction::Right] => Some(1),
+            [Instruction::Left] => Some(-1),
+            &[Instruction::Move(offset)] => Some(offset),
+            _ => None,
+        };
+        if let Some(step) = step
+            && info.is_optimizable(0..2)
+        {
+            *instruction = Instruction::Scan { step };
+            info.fold(2);
+        } else {
+            info.keep();
+            fold_scan_loops_in(body, info);
+        }
+    }
+}
+
+/// Replaces every [`Instruction::SetZero`] that is directly followed by `+`, `-` or
+/// [`Instruction::Add`], without a move in between, with a single [`Instruction::Set`] of the
+/// resulting value, e.g. `[-]+++` with a store of 3. The value wraps around like an 8-bit cell.
+///
+/// ```
+/// use brainlift::{Program, optimizer};
+///
+/// let mut program = Program::parse("[-]+++>[-]->[-]>+[[-]+-]").unwrap();
+/// optimizer::fold_clear_loops(&mut program);
+/// optimizer::fold_set_constants(&mut program);
+/// assert_eq!(
+///     program.tree(),
+///     "set 3\n>\nset 255\n>\nset zero\n>\n+\nloop\n  set zero\n"
+/// );
+/// ```
+pub fn fold_set_constants(program: &mut Program) {
+    let mut info = SourceInfo::take(program);
+    fold_set_constants_in(&mut program.instructions, &mut info);
+    info.finish(program);
+}
+
+fn fold_set_constants_in(instructions: &mut Vec<Instruction>, info: &mut SourceInfo) {
+    let mut folded = Vec::with_capacity(instructions.len());
+    let mut instructions_left = instructions.drain(..).peekable();
+
+    while let Some(mut instruction) = instructions_left.next() {
+        let optimizable = info.is_optimizable(0..1);
+        info.keep();
+        match &mut instruction {
+            Instruction::SetZero if optimizable => {
+                let mut value = 0u8;
+                while let Some(n) = instructions_left.peek().and_then(addition)
+                    && info.is_optimizable(0..1)
+                {
+                    value = value.wrapping_add_signed(n);
+                    instructions_left.next();
+                    info.remove(1);
+                }
+                if value != 0 {
+                    instruction = Instruction::Set(value);
+                }
+            }
+            Instruction::Loop(body) => fold_set_constants_in(body, info),
+            _ => {}
+        }
+        folded.push(instruction);
+    }
+
+    drop(instructions_left);
+    *instructions = folded;
+}
+
+/// Returns how much `instruction` adds to the current cell, if it is an addition.
+fn addition(instruction: &Instruction) -> Option<i8> {
+    match instruction {
+        Instruction::Increment => Some(1),
+        Instruction::Decrement => Some(-1),
+        Instruction::Add(n) => Some(*n),
+        _ => None,
+    }
+}
+
+/// Replaces every loop that decrements the current cell by one per iteration, returns to it, and
+/// otherwise only adds constants to nearby cells with an [`Instruction::MultiplyAdd`]. Such
+/// loops copy or multiply the current cell into the others, e.g. `[->+<]` or `[->++>-<<]`.
+///
+/// The additions are summed without wrapping, so this is correct for every cell size.
+///
+/// ```
+/// use brainlift::{Program, optimizer};
+///
+/// let mut program = Program::parse("[->+<][->++>-<<][-<<+>>][->+<<]").unwrap();
+/// optimizer::fold_multiply_loops(&mut program);
+/// let tree = program.tree();
+/// let lines: Vec<_> = tree.lines().collect();
+/// assert_eq!(
+///     lines[..3],
+///     ["multiply add 1 to 1", "multiply add 2 to 1, -1 to 2", "multiply add 1 to -2"]
+/// );
+/// // the pointer doesn't return to the counter, so the loop is left as it is
+/// assert_eq!(lines[3..], ["loop", "  -", "  >", "  +", "  <", "  <"]);
+/// ```
+pub fn fold_multiply_loops(program: &mut Program) {
+    let mut info = SourceInfo::take(program);
+    fold_multiply_loops_in(&mut program.instructions, &mut info);
+    info.finish(program);
+}
+
+fn fold_multiply_loops_in(instructions: &mut [Instruction], info: &mut SourceInfo) {
+    for instruction in instructions {
+        let Instruction::Loop(body) = instruction else {
+            info.keep();
+            continue;
+        };
+        // the body of a multiply loop has no loops, so these are all the instructions replaced
+        if let Some(targets) = multiply_targets(body)
+            && info.is_optimizable(0..1 + body.len())
+        {
+            info.fold(1 + body.len());
+            *instruction = Instruction::MultiplyAdd { targets };
+        } else {
+            info.keep();
+            fold_multiply_loops_in(body, info);
+        }
+    }
+}
+
+/// Returns the offsets and factors of the cells a multiply loop with the given body adds the
+/// current cell to, or `None` if it is no multiply loop.
+fn multiply_targets(body: &[Instruction]) -> Option<Vec<(isize, i8)>> {
+    let mut offset = 0isize;
+    let (mut leftmost, mut rightmost) = (0, 0);
+    let mut deltas = BTreeMap::<isize, i64>::new();
+    for instruction in body {
+        match instruction {
+            Instruction::Increment => *deltas.entry(offset).or_default() += 1,
+            Instruction::Decrement => *deltas.entry(offset).or_default() -= 1,
+            Instruction::Add(n) => *deltas.entry(offset).or_default() += *n as i64,
+            Instruction::Right => offset += 1,
+            Instruction::Left => offset -= 1,
+            Instruction::Move(moved) => offset += moved,
+            _ => return None,
+        }
+        leftmost = leftmost.min(offset);
+        rightmost = rightmost.max(offset);
+    }
+    if offset != 0 || deltas.remove(&0) != Some(-1) {
+        return None;
+    }
+
+    let targets: Vec<_> = deltas
+        .into_iter()
+        .filter(|&(_, delta)| delta != 0)
+        .map(|(offset, delta)| Some((offset, i8::try_from(delta).ok()?)))
+        .collect::<Option<_>>()?;
+    // the loop must reach its furthest cells with an addition, as moving there without a target
+    // would no longer fail when it's out of bounds
+    let reaches = |cell| cell == 0 || targets.iter().any(|&(offset, _)| offset == cell);
+    (!targets.is_empty() && reaches(leftmost) && reaches(rightmost)).then_some(targets)
+}
+
+/// Replaces every run of two or more consecutive instructions matching `in_run`, in the top level
+/// and in all loops, with the instruction returned by `fold`, or removes it if that is `None`.
+fn fold_runs(
+    instructions: &mut Vec<Instruction>,
+    info: &mut SourceInfo,
+    in_run: fn(&Instruction) -> bool,
+    fold: fn(&[Instruction]) -> Option<Instruction>,
+) {
+    let mut folded = Vec::with_capacity(instructions.len());
+    let mut run = Vec::new();
+
+    let end_run =
+        |folded: &mut Vec<Instruction>, run: &mut Vec<Instruction>, info: &mut SourceInfo| {
+            if run.len() == 1 {
+                folded.append(run);
+                info.keep();
+            } else if !run.is_empty() {
+                match fold(run) {
+                    Some(instruction) => {
+                        folded.push(instruction);
+                        info.fold(run.len());
+                    }
+                    None => info.remove(run.len()),
+                }
+                run.clear();
+            }
+        };
+
+    for mut instruction in instructions.drain(..) {
+        if in_run(&instruction) && info.is_optimizable(run.len()..run.len() + 1) {
+            run.push(instruction);
+            continue;
+        }
+        end_run(&mut folded, &mut run, info);
+        info.keep();
+        if let Instruction::Loop(body) = &mut instruction {
+            fold_runs(body, info, in_run, fold);
+        }
+        folded.push(instruction);
+    }
+    end_run(&mut folded, &mut run, info);
+
+    *instructions = folded;
+}
+
+/// The source positions and noopt flags of a program while a pass rewrites it. The pass walks
+/// the instructions in source order, loops before their bodies, and says what became of each
+/// one.
+struct SourceInfo {
+    old_spans: Option<Vec<Span>>,
+    old_noopt: Option<Vec<bool>>,
+    /// Index of the next instruction in source order.
+    next: usize,
+    spans: Vec<Span>,
+    noopt: Vec<bool>,
+}
+
+impl SourceInfo {
+    fn take(program: &mut Program) -> Self {
+        SourceInfo {
+            old_spans: program.spans.take(),
+            old_noopt: program.noopt.take(),
+            next: 0,
+            spans: Vec::new(),
+            noopt: Vec::new(),
+        }
+    }
+
+    /// Whether none of the instructions in `range`, counted from the next one, is in a noopt
+    /// region.
+    fn is_optimizable(&self, range: Range<usize>) -> bool {
+        let range = self.next + range.start..self.next + range.end;
+        self.old_noopt
+            .as_ref()
+            .is_none_or(|noopt| !noopt[range].contains(&true))
+    }
+
+    /// The next instruction is left as it is.
+    fn keep(&mut self) {
+        self.fold(1);
+    }
+
+    /// The next `n` instructions are replaced with one, which gets the position and the flag of
+    /// the first.
+    fn fold(&mut self, n: usize) {
+        if let Some(spans) = &self.old_spans {
+            self.spans.push(spans[self.next]);
+        }
+        if let Some(noopt) = &self.old_noopt {
+            self.noopt.push(noopt[self.next]);
+        }
+        self.next += n;
+    }
+
+    /// The next `n` instructions are removed.
+    fn remove(&mut self, n: usize) {
+        self.next += n;
+    }
+
+    fn finish(self, program: &mut Program) {
+        let old_len =
+            (self.old_spans.as_ref().map(Vec::len)).or(self.old_noopt.as_ref().map(Vec::len));
+        debug_assert!(
+            old_len.is_none_or(|len| len == self.next),
+            "every instruction should be accounted for"
+        );
+        program.spans = self.old_spans.map(|_| self.spans);
+        program.noopt = self.old_noopt.map(|_| self.noopt);
+    }
+}
+
+struct StaticEvaluator {
+    max_array_size: usize,
+    array: Vec<u8>,
+    pointer: usize,
+    steps: u64,
+    output: Vec<u8>,
+}
+
+impl StaticEvaluator {
+    fn evaluate(&mut self, instructions: &[Instruction]) -> Option<()> {
+        for instruction in instructions {
+            self.step()?;
+
+            match instruction {
+                Instruction::Increment => {
+                    self.array[self.pointer] = self.array[self.pointer].wrapping_add(1)
+                }
+                Instruction::Decrement => {
+                    self.array[self.pointer] = self.array[self.pointer].wrapping_sub(1)
+                }
+                Instruction::Add(n) => {
+                    self.array[self.pointer] = self.array[self.pointer].wrapping_add_signed(*n)
+                }
+                Instruction::SetZero => self.array[self.pointer] = 0,
+                Instruction::Set(value) => self.array[self.pointer] = *value,
+                Instruction::MultiplyAdd { targets } => {
+                    let value = self.array[self.pointer];
+                    if value == 0 {
+                        continue;
+                    }
+                    for &(offset, factor) in targets {
+                        let index = self
+                            .pointer
+                            .checked_add_signed(offset)
+                            .filter(|&index| index < self.max_array_size)?;
+                        if index >= self.array.len() {
+                            self.array.resize(index + 1, 0);
+                        }
+                        self.array[index] =
+                            self.array[index].wrapping_add(value.wrapping_mul(factor as u8));
+                    }
+                    self.array[self.pointer] = 0;
+                }
+                Instruction::Right => {
+                    self.pointer += 1;
+                    if self.pointer >= self.max_array_size {
+                        return None;
+                    }
+                    if self.pointer == self.array.len() {
+                        self.array.push(0);
+                    }
+                }
+                Instruction::Left => self.pointer = self.pointer.checked_sub(1)?,
+                Instruction::Move(offset) => self.move_by(*offset)?,
+                Instruction::Scan { step } => {
+                    while self.array[self.pointer] != 0 {
+                        self.step()?;
+                        self.move_by(*step)?;
+                    }
+                }
+                Instruction::Output => self.output.push(self.array[self.pointer]),
+                Instruction::OutputBytes(bytes) => self.output.extend_from_slice(bytes),
+                Instruction::InitCells {
+                    base_offset,
+                    values,
+                } => {
+                    let start = self.pointer + base_offset;
+                    let end = start + values.len();
+                    if end > self.max_array_size {
+                        return None;
+                    }
+                    if end > self.array.len() {
+                        self.array.resize(end, 0);
+                    }
+                    self.array[start..end].copy_from_slice(values);
+                }
+                Instruction::Loop(instructions) => {
+                    while self.array[self.pointer] != 0 {
+                        self.step()?;
+                        self.evaluate(instructions)?;
+                    }
+                }
+                Instruction::Input | Instruction::Debug => return None,
+            }
+        }
+
+        Some(())
+    }
+
+    fn move_by(&mut self, offset: isize) -> Option<()> {
+        self.pointer = self
+            .pointer
+            .checked_add_signed(offset)
+            .filter(|&pointer| pointer < self.max_array_size)?;
+        if self.pointer >= self.array.len() {
+            self.array.resize(self.pointer + 1, 0);
+        }
+        Some(())
+    }
+
+    fn step(&mut self) -> Option<()> {
+        self.steps += 1;
+        (self.steps <= STATIC_EVALUATION_STEP_LIMIT).then_some(())
+    }
+}
@@ -1,21 +1,112 @@
-use crate::program::{Instruction, Program};
+use crate::program::{Instruction, Program, Span};
 
 pub struct Parser<'a> {
     source: &'a [u8],
     index: usize,
+    max_instructions: Option<usize>,
+    embedded_input: bool,
+    instructions: usize,
+    /// Positions of the instructions parsed so far, if they are recorded.
+    spans: Option<Vec<Span>>,
+    /// Whether each instruction parsed so far is in a noopt region, and whether `index` is.
+    noopt: Vec<bool>,
+    in_noopt: bool,
+    /// Line of `index` and the index its line starts at.
+    line: usize,
+    line_start: usize,
 }
 
 const INSTRUCTIONS: [u8; 9] = [b'+', b'-', b'>', b'<', b'.', b',', b'[', b']', b'#'];
 
+const TAPE_DIRECTIVE: &str = ";tape";
+
+/// Comments enclosing instructions that the optimization passes leave as they are.
+const NOOPT_BEGIN: &str = "; noopt-begin";
+const NOOPT_END: &str = "; noopt-end";
+
 impl<'a> Parser<'a> {
     pub fn new(source: &'a str) -> Self {
         Self {
             source: source.as_bytes(),
             index: 0,
+            max_instructions: None,
+            embedded_input: false,
+            instructions: 0,
+            spans: None,
+            noopt: Vec::new(),
+            in_noopt: false,
+            line: 1,
+            line_start: 0,
         }
     }
 
+    /// Record the source position of every instruction in [`Program::spans`], e.g. to point
+    /// runtime errors at the instruction that caused them.
+    pub fn with_spans(mut self, enabled: bool) -> Self {
+        self.spans = enabled.then(Vec::new);
+        self
+    }
+
+    /// Fail with [`ParserError::TooManyInstructions`] once the program contains more than
+    /// `max_instructions` instructions, counting each loop as one besides its body.
+    ///
+    /// ```
+    /// use brainlift::{Parser, parser::ParserError};
+    ///
+    /// // three instructions: `+`, the loop and the `-` in it
+    /// let source = "+[-] comments don't count";
+    /// assert!(Parser::new(source).with_max_instructions(Some(3)).parse().is_ok());
+    ///
+    /// let error = Parser::new(source).with_max_instructions(Some(2)).parse().unwrap_err();
+    /// assert!(matches!(error, ParserError::TooManyInstructions(2)));
+    /// ```
+    pub fn with_max_instructions(mut self, max_instructions: Option<usize>) -> Self {
+        self.max_instructions = max_instructions;
+        self
+    }
+
+    /// End the program at the first `!` and keep the rest of the source as its
+    /// [`Program::input`], as some brainfuck environments do. Otherwise, `!` is a comment like
+    /// any other character.
+    ///
+    /// ```
+    /// use brainlift::Parser;
+    ///
+    /// let program = Parser::new(",[.,]!hi!").with_embedded_input(true).parse().unwrap();
+    /// assert_eq!(program.to_string(), ",[.,]");
+    /// assert_eq!(program.input.as_deref(), Some(&b"hi!"[..]));
+    /// ```
+    pub fn with_embedded_input(mut self, enabled: bool) -> Self {
+        self.embedded_input = enabled;
+        self
+    }
+
+    /// Parses the source into a [`Program`]. A `;tape N` directive on the first line requests a
+    /// tape of `N` cells, which must be positive.
+    ///
+    /// ```
+    /// use brainlift::{Parser, parser::ParserError};
+    ///
+    /// let program = Parser::new(";tape 5000\n+[-]").parse().unwrap();
+    /// assert_eq!(program.tape_size, Some(5000));
+    /// assert_eq!(program.to_string(), "+[-]");
+    ///
+    /// let program = Parser::new("+[-]").parse().unwrap();
+    /// assert_eq!(program.tape_size, None);
+    ///
+    /// let error = Parser::new(";tape 0\n+[-]").parse().unwrap_err();
+    /// assert!(matches!(error, ParserError::InvalidTapeDirective));
+    /// ```
     pub fn parse(&mut self) -> Result<Program, ParserError> {
+        let input = self.split_input();
+        let tape_size = self.tape_directive()?;
+        self.check_brackets()?;
+
+        // `advance` skips comments after an instruction, so we need to skip a leading one here
+        if !INSTRUCTIONS.contains(&self.current()) {
+            self.advance();
+        }
+
         let mut instructions = Vec::new();
         while !self.is_at_end() {
             match self.instruction() {
@@ -25,10 +116,96 @@ impl<'a> Parser<'a> {
             self.advance();
         }
 
-        Ok(Program { instructions })
+        Ok(Program {
+            instructions,
+            tape_size,
+            spans: self.spans.take(),
+            noopt: self
+                .noopt
+                .contains(&true)
+                .then(|| std::mem::take(&mut self.noopt)),
+            input,
+        })
+    }
+
+    /// Cuts the source off at the first `!` if embedded input is enabled, returning the rest.
+    fn split_input(&mut self) -> Option<Vec<u8>> {
+        if !self.embedded_input {
+            return None;
+        }
+        let end = self.source.iter().position(|&c| c == b'!')?;
+        let input = self.source[end + 1..].to_vec();
+        self.source = &self.source[..end];
+        Some(input)
+    }
+
+    /// Reads a `;tape N` directive from the first line of the source, if there is one.
+    /// The directive contains no instruction characters, so it doesn't need to be skipped.
+    fn tape_directive(&self) -> Result<Option<u32>, ParserError> {
+        let first_line = self
+            .source
+            .split(|&c| c == b'\n')
+            .next()
+            .unwrap_or_default();
+        let Some(size) = first_line.strip_prefix(TAPE_DIRECTIVE.as_bytes()) else {
+            return Ok(None);
+        };
+
+        let size = std::str::from_utf8(size)
+            .ok()
+            .and_then(|s| s.trim().parse::<u32>().ok());
+        match size {
+            Some(size) if size > 0 => Ok(Some(size)),
+            _ => Err(ParserError::InvalidTapeDirective),
+        }
+    }
+
+    /// Checks that every bracket in the source has a partner, so that building the loops
+    /// afterwards can't fail. Reports the first `]` without an opening bracket or, if there
+    /// is none, the outermost `[` left unclosed at the end.
+    fn check_brackets(&self) -> Result<(), ParserError> {
+        let (mut line, mut line_start) = (1, 0);
+        let mut depth = 0usize;
+        let mut outermost = (0, 0);
+        for (index, &c) in self.source.iter().enumerate() {
+            let column = index - line_start + 1;
+            match c {
+                b'\n' => (line, line_start) = (line + 1, index + 1),
+                b'[' => {
+                    if depth == 0 {
+                        outermost = (line, column);
+                    }
+                    depth += 1;
+                }
+                b']' if depth == 0 => {
+                    return Err(ParserError::UnexpectedClosingBracket(line, column));
+                }
+                b']' => depth -= 1,
+                _ => {}
+            }
+        }
+
+        if depth > 0 {
+            return Err(ParserError::MismatchedBracket(outermost.0, outermost.1));
+        }
+        Ok(())
     }
 
     fn instruction(&mut self) -> Result<Instruction, ParserError> {
+        self.instructions += 1;
+        if let Some(max) = self.max_instructions
+            && self.instructions > max
+        {
+            return Err(ParserError::TooManyInstructions(max));
+        }
+        if let Some(spans) = &mut self.spans {
+            spans.push(Span {
+                line: self.line,
+                column: self.index - self.line_start + 1,
+            });
+        }
+        self.noopt.push(self.in_noopt);
+
         match self.current() {
             b'#' => Ok(Instruction::Debug),
             b'+' => Ok(Instruction::Increment),
@@ -38,21 +215,16 @@ impl<'a> Parser<'a> {
             b'.' => Ok(Instruction::Output),
             b',' => Ok(Instruction::Input),
             b'[' => {
-                let index = self.index;
                 self.advance();
                 let mut nested = Vec::new();
-                while !self.is_at_end() && self.current() != b']' {
-                    let inst = self.instruction()?;
-                    nested.push(inst);
-
+                // `check_brackets` made sure that this loop is closed before the end
+                while self.current() != b']' {
+                    nested.push(self.instruction()?);
                     self.advance();
                 }
-                if self.current() != b']' {
-                    let line = self.line_number(index);
-                    return Err(ParserError::MismatchedBracket(line));
-                }
                 Ok(Instruction::Loop(nested))
             }
+            // loops consume their own closing bracket, and `check_brackets` rejected any others
             _ => unreachable!(),
         }
     }
@@ -71,7 +243,16 @@ impl<'a> Parser<'a> {
                 break;
             }
 
+            // the `-` in a marker isn't an instruction, so skip to its last character
+            if let Some((len, begins)) = noopt_marker(self.source, self.index) {
+                self.in_noopt = begins;
+                self.index += len - 1;
+            }
             self.index += 1;
+            if self.source[self.index - 1] == b'\n' {
+                self.line += 1;
+                self.line_start = self.index;
+            }
 
             if INSTRUCTIONS.contains(&self.current()) {
                 break;
@@ -82,22 +263,87 @@ impl<'a> Parser<'a> {
     fn is_at_end(&self) -> bool {
         self.index >= self.source.len()
     }
+}
+
+/// The instruction characters of `source`, with all comments removed.
+pub fn minify(source: &str) -> Vec<u8> {
+    let source = source.as_bytes();
+    let mut minified = Vec::new();
+    let mut index = 0;
+    while index < source.len() {
+        if let Some((len, _)) = noopt_marker(source, index) {
+            index += len;
+            continue;
+        }
+        if INSTRUCTIONS.contains(&source[index]) {
+            minified.push(source[index]);
+        }
+        index += 1;
+    }
+    minified
+}
 
-    fn line_number(&self, index: usize) -> usize {
-        self.source[..index].iter().filter(|&&c| c == b'\n').count() + 1
+/// Returns the length of the noopt marker at `index` of `source`, if there is one, and whether it
+/// begins a region.
+fn noopt_marker(source: &[u8], index: usize) -> Option<(usize, bool)> {
+    let rest = &source[index..];
+    if rest.starts_with(NOOPT_BEGIN.as_bytes()) {
+        Some((NOOPT_BEGIN.len(), true))
+    } else if rest.starts_with(NOOPT_END.as_bytes()) {
+        Some((NOOPT_END.len(), false))
+    } else {
+        None
     }
 }
 
 #[derive(Debug)]
 pub enum ParserError {
-    MismatchedBracket(usize),
+    /// Line and column of the outermost unclosed `[`.
+    ///
+    /// ```
+    /// use brainlift::{Parser, parser::ParserError};
+    ///
+    /// let error = Parser::new("[[+]").parse().unwrap_err();
+    /// assert!(matches!(error, ParserError::MismatchedBracket(1, 1)));
+    ///
+    /// let error = Parser::new("+\n [\n  [\n   [-]").parse().unwrap_err();
+    /// assert!(matches!(error, ParserError::MismatchedBracket(2, 2)));
+    /// ```
+    MismatchedBracket(usize, usize),
+    /// Line and column of the first `]` without an opening bracket. It's reported even if
+    /// an unclosed `[` follows.
+    ///
+    /// ```
+    /// use brainlift::{Parser, parser::ParserError};
+    ///
+    /// let error = Parser::new("+[-]]").parse().unwrap_err();
+    /// assert!(matches!(error, ParserError::UnexpectedClosingBracket(1, 5)));
+    ///
+    /// let error = Parser::new("[\n]\n ]\n[").parse().unwrap_err();
+    /// assert!(matches!(error, ParserError::UnexpectedClosingBracket(3, 2)));
+    /// ```
+    UnexpectedClosingBracket(usize, usize),
+    InvalidTapeDirective,
+    TooManyInstructions(usize),
 }
 
 impl std::fmt::Display for ParserError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            ParserError::MismatchedBracket(line) => {
-                write!(f, "mismatched bracket in line {line}")
+            ParserError::MismatchedBracket(line, column) => {
+                write!(f, "unclosed '[' at line {line}, column {column}")
+            }
+            ParserError::UnexpectedClosingBracket(line, column) => {
+                write!(f, "unexpected ']' at line {line}, column {column}")
+            }
+            ParserError::InvalidTapeDirective => {
+                write!(
+                    f,
+                    "expected a positive tape size after `{TAPE_DIRECTIVE}` in line 1"
+                )
+            }
+            ParserError::TooManyInstructions(max) => {
+                write!(f, "program has more than {max} instructions")
             }
         }
     }
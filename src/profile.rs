@@ -0,0 +1,86 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::program::{Instruction, Program};
+
+/// Longest loop source shown in the report, longer loops are cut off.
+const MAX_LOOP_SOURCE_LEN: usize = 60;
+
+/// Execution counts of a run, as collected by [`crate::interpreter::Interpreter::with_profile`].
+#[derive(Debug, Default)]
+pub struct Profile {
+    /// How often each kind of instruction was executed, by [`Instruction::name`]. A loop counts
+    /// once per time it is entered.
+    pub executed: BTreeMap<&'static str, u64>,
+    /// Iterations of each loop, by the loop's address in the program.
+    iterations: HashMap<usize, u64>,
+}
+
+impl Profile {
+    pub(crate) fn execute(&mut self, instruction: &Instruction) {
+        *self.executed.entry(instruction.name()).or_default() += 1;
+    }
+
+    pub(crate) fn iterate(&mut self, instruction: &Instruction) {
+        *self.iterations.entry(address(instruction)).or_default() += 1;
+    }
+
+    /// Total number of loop iterations.
+    pub fn loop_iterations(&self) -> u64 {
+        self.iterations.values().sum()
+    }
+
+    /// Returns the loop of `program` with the most iterations, and their number. `program` must
+    /// be the program the profile was collected from.
+    pub fn hottest_loop<'p>(&self, program: &'p Program) -> Option<(&'p Instruction, u64)> {
+        let mut hottest = None;
+        visit_loops(&program.instructions, &mut |instruction| {
+            let count = self.iterations.get(&address(instruction)).copied();
+            if let Some(count) = count
+                && hottest.is_none_or(|(_, max)| count > max)
+            {
+                hottest = Some((instruction, count));
+            }
+        });
+        hottest
+    }
+
+    /// Prints the counts as a table to stderr, most executed first.
+    pub fn print(&self, program: &Program) {
+        let mut executed: Vec<_> = self.executed.iter().collect();
+        executed.sort_by(|a, b| b.1.cmp(a.1));
+
+        eprintln!("{:<16} {:>14}", "instruction", "executed");
+        for (name, count) in executed {
+            eprintln!("{name:<16} {count:>14}");
+        }
+
+        eprintln!();
+        eprintln!(
+            "total:           {:>14}",
+            self.executed.values().sum::<u64>()
+        );
+        eprintln!("loop iterations: {:>14}", self.loop_iterations());
+        if let Some((instruction, count)) = self.hottest_loop(program) {
+            let mut source = instruction.to_string();
+            if source.len() > MAX_LOOP_SOURCE_LEN {
+                source.truncate(MAX_LOOP_SOURCE_LEN);
+                source.push_str("...");
+            }
+            eprintln!("hottest loop:    {count:>14} iterations of {source}");
+        }
+    }
+}
+
+fn address(instruction: &Instruction) -> usize {
+    instruction as *const Instruction as usize
+}
+
+/// Calls `f` with every loop in `instructions`, outer loops before the loops they contain.
+fn visit_loops<'p>(instructions: &'p [Instruction], f: &mut impl FnMut(&'p Instruction)) {
+    for instruction in instructions {
+        if let Instruction::Loop(body) = instruction {
+            f(instruction);
+            visit_loops(body, f);
+        }
+    }
+}
@@ -1,6 +1,149 @@
+use crate::parser::{Parser, ParserError};
+
 #[derive(Debug)]
 pub struct Program {
     pub instructions: Vec<Instruction>,
+    /// Tape size requested by a `;tape N` directive at the start of the source.
+    pub tape_size: Option<u32>,
+    /// Source position of every instruction, in the order they appear in the source with loops
+    /// before their bodies, if the parser recorded them (see [`crate::Parser::with_spans`]).
+    /// Optimization passes keep them, giving an instruction that replaces several the position
+    /// of the first of them.
+    pub spans: Option<Vec<Span>>,
+    /// Whether each instruction, in the same order as the spans, is in a region enclosed by
+    /// `; noopt-begin` and `; noopt-end` comments, which the optimization passes leave as it is.
+    /// `None` if the source has no such region.
+    pub noopt: Option<Vec<bool>>,
+    /// Input for `,` that followed a `!` in the source, if the parser looked for one (see
+    /// [`crate::Parser::with_embedded_input`]).
+    pub input: Option<Vec<u8>>,
+}
+
+impl Program {
+    /// Parses `source` with the default settings of [`crate::Parser`], which is needed only to
+    /// set limits or record source positions. The same is available as [`str::parse`].
+    ///
+    /// ```
+    /// use brainlift::Program;
+    ///
+    /// let program = Program::parse("++[>+<-] add two").unwrap();
+    /// assert_eq!(program.instructions.len(), 3);
+    ///
+    /// let program: Program = "+[-]".parse().unwrap();
+    /// assert_eq!(program.instructions.len(), 2);
+    /// assert!(Program::parse("+[").is_err());
+    /// ```
+    pub fn parse(source: &str) -> Result<Program, ParserError> {
+        Parser::new(source).parse()
+    }
+
+    /// Returns the source position of the instruction found by following `path` from the top
+    /// level, each element being the index in the enclosing instructions and all but the last
+    /// naming a loop, or `None` if the path leads nowhere or the program has no positions.
+    ///
+    /// ```
+    /// use brainlift::{Parser, program::Span};
+    ///
+    /// let program = Parser::new("+[>\n[-]<]").with_spans(true).parse().unwrap();
+    /// assert_eq!(program.span_at(&[1, 1, 0]), Some(Span { line: 2, column: 2 }));
+    /// assert_eq!(program.span_at(&[1, 2]), Some(Span { line: 2, column: 4 }));
+    /// assert_eq!(program.span_at(&[2]), None);
+    /// ```
+    pub fn span_at(&self, path: &[usize]) -> Option<Span> {
+        let spans = self.spans.as_ref()?;
+        let mut instructions = &self.instructions[..];
+        let mut index = 0;
+        for (depth, &position) in path.iter().enumerate() {
+            index += instruction_count(instructions.get(..position)?);
+            match instructions.get(position)? {
+                Instruction::Loop(body) if depth + 1 < path.len() => {
+                    // the loop precedes its body in source order
+                    index += 1;
+                    instructions = body;
+                }
+                _ if depth + 1 < path.len() => return None,
+                _ => {}
+            }
+        }
+        spans.get(index).copied()
+    }
+
+    /// Renders the instructions one per line, with loop bodies indented, e.g. to see what the
+    /// optimization passes turned the program into.
+    ///
+    /// ```
+    /// use brainlift::{Program, optimizer};
+    ///
+    /// let mut program = Program::parse("+++[>[-]<-]").unwrap();
+    /// optimizer::fold_additions(&mut program);
+    /// optimizer::fold_clear_loops(&mut program);
+    /// assert_eq!(program.tree(), "add 3\nloop\n  >\n  set zero\n  <\n  -\n");
+    /// ```
+    pub fn tree(&self) -> String {
+        let mut tree = String::new();
+        write_tree(&self.instructions, 0, &mut tree);
+        tree
+    }
+}
+
+fn write_tree(instructions: &[Instruction], depth: usize, tree: &mut String) {
+    for instruction in instructions {
+        tree.push_str(&"  ".repeat(depth));
+        if let Instruction::Loop(body) = instruction {
+            tree.push_str("loop\n");
+            write_tree(body, depth + 1, tree);
+            continue;
+        }
+
+        tree.push_str(instruction.name());
+        match instruction {
+            Instruction::Add(n) => tree.push_str(&format!(" {n}")),
+            Instruction::Move(offset) => tree.push_str(&format!(" {offset}")),
+            Instruction::Set(value) => tree.push_str(&format!(" {value}")),
+            Instruction::Scan { step } => tree.push_str(&format!(" by {step}")),
+            Instruction::MultiplyAdd { targets } => {
+                let targets: Vec<_> = targets
+                    .iter()
+                    .map(|(offset, factor)| format!("{factor} to {offset}"))
+                    .collect();
+                tree.push_str(&format!(" {}", targets.join(", ")));
+            }
+            Instruction::OutputBytes(bytes) => {
+                tree.push_str(&format!(" \"{}\"", bytes.escape_ascii()));
+            }
+            Instruction::InitCells {
+                base_offset,
+                values,
+            } => tree.push_str(&format!(" from {base_offset} to {values:?}")),
+            _ => {}
+        }
+        tree.push('\n');
+    }
+}
+
+/// Returns the number of instructions in `instructions`, including those in loop bodies.
+fn instruction_count(instructions: &[Instruction]) -> usize {
+    instructions
+        .iter()
+        .map(|instruction| match instruction {
+            Instruction::Loop(body) => 1 + instruction_count(body),
+            _ => 1,
+        })
+        .sum()
+}
+
+/// Position of an instruction's character in the source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub line: usize,
+    /// 1-based byte offset from the start of the line.
+    pub column: usize,
+}
+
+impl std::fmt::Display for Span {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}, column {}", self.line, self.column)
+    }
 }
 
 #[derive(Debug)]
@@ -13,4 +156,363 @@ pub enum Instruction {
     Input,
     Loop(Vec<Instruction>),
     Debug,
+    /// Adds to the current cell, wrapping around. Replaces runs of `+` and `-`, see
+    /// [`crate::optimizer::fold_additions`].
+    Add(i8),
+    /// Moves the pointer by the given offset. Replaces runs of `>` and `<`, see
+    /// [`crate::optimizer::fold_moves`].
+    Move(isize),
+    /// Sets the current cell to zero. Replaces the loops `[-]` and `[+]`, see
+    /// [`crate::optimizer::fold_clear_loops`].
+    SetZero,
+    /// Sets the current cell to a constant. Replaces an [`Instruction::SetZero`] directly followed
+    /// by additions, like `[-]+++`, see [`crate::optimizer::fold_set_constants`].
+    Set(u8),
+    /// Writes a fixed sequence of bytes, as computed by [`crate::optimizer::evaluate_statically`].
+    OutputBytes(Vec<u8>),
+    /// Sets the cells starting `base_offset` cells right of the pointer to `values`, without
+    /// moving the pointer, as computed by [`crate::optimizer::fold_initialization`].
+    InitCells {
+        base_offset: usize,
+        values: Vec<u8>,
+    },
+    /// Adds the current cell times `factor` to the cell `offset` cells from the pointer for each
+    /// target, wrapping around, then sets the current cell to zero. Replaces loops like `[->+<]`
+    /// and `[->++>-<<]`, see [`crate::optimizer::fold_multiply_loops`].
+    MultiplyAdd {
+        targets: Vec<(isize, i8)>,
+    },
+    /// Moves the pointer by `step` until it is on a zero cell, checking the current cell first.
+    /// Replaces the loops `[>]`, `[<]` and ones with a single larger move, see
+    /// [`crate::optimizer::fold_scan_loops`]. Like the loop, it fails by moving out of bounds if
+    /// no zero cell is left in its direction.
+    Scan {
+        step: isize,
+    },
+}
+
+impl Instruction {
+    /// A short name for the kind of instruction, e.g. for reports.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Instruction::Increment => "+",
+            Instruction::Decrement => "-",
+            Instruction::Right => ">",
+            Instruction::Left => "<",
+            Instruction::Output => ".",
+            Instruction::Input => ",",
+            Instruction::Loop(_) => "[",
+            Instruction::Debug => "#",
+            Instruction::Add(_) => "add",
+            Instruction::Move(_) => "move",
+            Instruction::SetZero => "set zero",
+            Instruction::Set(_) => "set",
+            Instruction::OutputBytes(_) => "output bytes",
+            Instruction::InitCells { .. } => "init cells",
+            Instruction::MultiplyAdd { .. } => "multiply add",
+            Instruction::Scan { .. } => "scan",
+        }
+    }
+
+    /// The character the parser reads as this instruction, or `None` for loops, which span two
+    /// characters, and the instructions introduced by optimization passes.
+    pub fn to_char(&self) -> Option<char> {
+        match self {
+            Instruction::Increment => Some('+'),
+            Instruction::Decrement => Some('-'),
+            Instruction::Right => Some('>'),
+            Instruction::Left => Some('<'),
+            Instruction::Output => Some('.'),
+            Instruction::Input => Some(','),
+            Instruction::Debug => Some('#'),
+            _ => None,
+        }
+    }
+}
+
+/// Writes the instruction as source code that parses to instructions with the same effect.
+/// [`Instruction::OutputBytes`] has no source form, as producing the bytes would take cells to
+/// compute them in. It's written as a description in parentheses instead, which the parser
+/// skips as a comment.
+impl std::fmt::Display for Instruction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Instruction::Loop(body) => {
+                write!(f, "[")?;
+                body.iter()
+                    .try_for_each(|instruction| write!(f, "{instruction}"))?;
+                write!(f, "]")
+            }
+            Instruction::Add(n) if *n < 0 => write!(f, "{}", "-".repeat(n.unsigned_abs().into())),
+            Instruction::Add(n) => write!(f, "{}", "+".repeat(*n as usize)),
+            Instruction::Move(offset) if *offset < 0 => {
+                write!(f, "{}", "<".repeat(offset.unsigned_abs()))
+            }
+            Instruction::Move(offset) => write!(f, "{}", ">".repeat(*offset as usize)),
+            Instruction::SetZero => write!(f, "[-]"),
+            Instruction::Set(value) => write!(f, "[-]{}", "+".repeat((*value).into())),
+            Instruction::MultiplyAdd { targets } => {
+                write!(f, "[-")?;
+                let mut offset = 0;
+                for &(target, factor) in targets {
+                    write!(f, "{}", Instruction::Move(target - offset))?;
+                    write!(f, "{}", Instruction::Add(factor))?;
+                    offset = target;
+                }
+                write!(f, "{}]", Instruction::Move(-offset))
+            }
+            Instruction::Scan { step } => write!(f, "[{}]", Instruction::Move(*step)),
+            Instruction::OutputBytes(bytes) => write!(f, "(output of {} bytes)", bytes.len()),
+            Instruction::InitCells {
+                base_offset,
+                values,
+            } => {
+                write!(f, "{}", Instruction::Move(*base_offset as isize))?;
+                for &value in values {
+                    write!(f, "[-]{}>", "+".repeat(value.into()))?;
+                }
+                write!(
+                    f,
+                    "{}",
+                    Instruction::Move(-((base_offset + values.len()) as isize))
+                )
+            }
+            _ => write!(
+                f,
+                "{}",
+                self.to_char()
+                    .expect("all other instructions are characters")
+            ),
+        }
+    }
+}
+
+impl std::str::FromStr for Program {
+    type Err = ParserError;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        Program::parse(source)
+    }
+}
+
+/// Writes the instructions as source code, without the comments and the `;tape` directive of
+/// the source they were parsed from. Parsing the result gives back the same instructions.
+///
+/// ```
+/// use brainlift::Parser;
+///
+/// let program = Parser::new("add two: ++ [loop>+<-] print .").parse().unwrap();
+/// assert_eq!(program.to_string(), "++[>+<-].");
+///
+/// // round trip random programs made of all instructions
+/// let mut seed: u64 = 1;
+/// let mut random = |n: u64| {
+///     seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+///     (seed >> 33) % n
+/// };
+/// for _ in 0..200 {
+///     let mut source = String::new();
+///     let mut depth = 0;
+///     for _ in 0..random(50) {
+///         match random(10) {
+///             8 => {
+///                 source.push('[');
+///                 depth += 1;
+///             }
+///             9 if depth > 0 => {
+///                 source.push(']');
+///                 depth -= 1;
+///             }
+///             i => source.push(b"+-<>.,# x"[i as usize % 9] as char),
+///         }
+///     }
+///     source.push_str(&"]".repeat(depth));
+///
+///     let displayed = Parser::new(&source).parse().unwrap().to_string();
+///     let reparsed = Parser::new(&displayed).parse().unwrap().to_string();
+///     assert_eq!(displayed, reparsed);
+///     assert_eq!(displayed, source.replace([' ', 'x'], ""));
+/// }
+/// ```
+impl std::fmt::Display for Program {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.instructions
+            .iter()
+            .try_for_each(|instruction| write!(f, "{instruction}"))
+    }
+}
+
+/// A [`Program`] with its loops flattened into `LoopStart`/`LoopEnd` markers, so that it can be
+/// executed without recursion.
+#[derive(Debug)]
+pub struct FlatProgram<'a> {
+    pub instructions: Vec<FlatInstruction<'a>>,
+    /// For each `LoopStart` or `LoopEnd`, the index of the matching marker.
+    pub jumps: Vec<usize>,
+    /// For each instruction, its source position, if the program has them. Both markers of a
+    /// loop have the position of its `[`.
+    pub spans: Option<Vec<Span>>,
+}
+
+#[derive(Debug)]
+pub enum FlatInstruction<'a> {
+    Increment,
+    Decrement,
+    Right,
+    Left,
+    Output,
+    Input,
+    Debug,
+    Add(i8),
+    Move(isize),
+    SetZero,
+    Set(u8),
+    OutputBytes(&'a [u8]),
+    InitCells {
+        base_offset: usize,
+        values: &'a [u8],
+    },
+    MultiplyAdd {
+        targets: &'a [(isize, i8)],
+    },
+    Scan {
+        step: isize,
+    },
+    /// Jumps past the matching `LoopEnd` if the current cell is zero.
+    LoopStart,
+    /// Jumps back to the matching `LoopStart` if the current cell is not zero.
+    LoopEnd,
+}
+
+impl<'a> From<&'a Program> for FlatProgram<'a> {
+    fn from(program: &'a Program) -> Self {
+        let mut flat = FlatProgram {
+            instructions: Vec::new(),
+            jumps: Vec::new(),
+            spans: program.spans.as_ref().map(|_| Vec::new()),
+        };
+        flat.flatten(&program.instructions, program.spans.as_deref(), &mut 0);
+        flat
+    }
+}
+
+impl<'a> FlatProgram<'a> {
+    /// Returns the source position of the instruction at `index`, if the program has them.
+    pub fn span(&self, index: usize) -> Option<Span> {
+        self.spans.as_ref()?.get(index).copied()
+    }
+
+    /// Renders the instructions one per line, numbered by their index, using the names of
+    /// [`Program::tree`]. Each loop marker shows the index of the instruction it continues at
+    /// when it jumps.
+    ///
+    /// ```
+    /// use brainlift::{Program, optimizer, program::FlatProgram};
+    ///
+    /// let mut program = Program::parse("++[>[-]<-].").unwrap();
+    /// optimizer::fold_clear_loops(&mut program);
+    /// let listing = FlatProgram::from(&program).listing();
+    /// assert_eq!(
+    ///     listing,
+    ///     "0  +\n1  +\n2  loop start, to 8 if zero\n3  >\n4  set zero\n5  <\n6  -\n\
+    ///      7  loop end, to 3 if not zero\n8  .\n"
+    /// );
+    /// ```
+    pub fn listing(&self) -> String {
+        let width = self.instructions.len().saturating_sub(1).to_string().len();
+        let mut listing = String::new();
+        for (index, instruction) in self.instructions.iter().enumerate() {
+            let text = match instruction {
+                FlatInstruction::Increment => "+".to_string(),
+                FlatInstruction::Decrement => "-".to_string(),
+                FlatInstruction::Right => ">".to_string(),
+                FlatInstruction::Left => "<".to_string(),
+                FlatInstruction::Output => ".".to_string(),
+                FlatInstruction::Input => ",".to_string(),
+                FlatInstruction::Debug => "#".to_string(),
+                FlatInstruction::Add(n) => format!("add {n}"),
+                FlatInstruction::Move(offset) => format!("move {offset}"),
+                FlatInstruction::SetZero => "set zero".to_string(),
+                FlatInstruction::Set(value) => format!("set {value}"),
+                FlatInstruction::OutputBytes(bytes) => {
+                    format!("output bytes \"{}\"", bytes.escape_ascii())
+                }
+                FlatInstruction::InitCells {
+                    base_offset,
+                    values,
+                } => format!("init cells from {base_offset} to {values:?}"),
+                FlatInstruction::MultiplyAdd { targets } => {
+                    let targets: Vec<_> = targets
+                        .iter()
+                        .map(|(offset, factor)| format!("{factor} to {offset}"))
+                        .collect();
+                    format!("multiply add {}", targets.join(", "))
+                }
+                FlatInstruction::Scan { step } => format!("scan by {step}"),
+                FlatInstruction::LoopStart => {
+                    format!("loop start, to {} if zero", self.jumps[index] + 1)
+                }
+                FlatInstruction::LoopEnd => {
+                    format!("loop end, to {} if not zero", self.jumps[index] + 1)
+                }
+            };
+            listing.push_str(&format!("{index:>width$}  {text}\n"));
+        }
+        listing
+    }
+
+    /// Appends `instructions`, the first of which is instruction `next` of the program in source
+    /// order.
+    fn flatten(
+        &mut self,
+        instructions: &'a [Instruction],
+        spans: Option<&[Span]>,
+        next: &mut usize,
+    ) {
+        for instruction in instructions {
+            let span = spans.and_then(|spans| spans.get(*next).copied());
+            *next += 1;
+            let flat = match instruction {
+                Instruction::Increment => FlatInstruction::Increment,
+                Instruction::Decrement => FlatInstruction::Decrement,
+                Instruction::Right => FlatInstruction::Right,
+                Instruction::Left => FlatInstruction::Left,
+                Instruction::Output => FlatInstruction::Output,
+                Instruction::Input => FlatInstruction::Input,
+                Instruction::Debug => FlatInstruction::Debug,
+                Instruction::Add(n) => FlatInstruction::Add(*n),
+                Instruction::Move(offset) => FlatInstruction::Move(*offset),
+                Instruction::SetZero => FlatInstruction::SetZero,
+                Instruction::Set(value) => FlatInstruction::Set(*value),
+                Instruction::OutputBytes(bytes) => FlatInstruction::OutputBytes(bytes),
+                Instruction::InitCells {
+                    base_offset,
+                    values,
+                } => FlatInstruction::InitCells {
+                    base_offset: *base_offset,
+                    values,
+                },
+                Instruction::MultiplyAdd { targets } => FlatInstruction::MultiplyAdd { targets },
+                Instruction::Scan { step } => FlatInstruction::Scan { step: *step },
+                Instruction::Loop(body) => {
+                    let start = self.push(FlatInstruction::LoopStart, span);
+                    self.flatten(body, spans, next);
+                    let end = self.push(FlatInstruction::LoopEnd, span);
+                    self.jumps[start] = end;
+                    self.jumps[end] = start;
+                    continue;
+                }
+            };
+            self.push(flat, span);
+        }
+    }
+
+    fn push(&mut self, instruction: FlatInstruction<'a>, span: Option<Span>) -> usize {
+        self.instructions.push(instruction);
+        self.jumps.push(0);
+        if let (Some(spans), Some(span)) = (&mut self.spans, span) {
+            spans.push(span);
+        }
+        self.instructions.len() - 1
+    }
 }
@@ -0,0 +1,318 @@
+//! Recordings of interpreter runs, for reproducible bug reports.
+//!
+//! A recording is a text header followed by the raw input the program read:
+//!
+//! ```text
+//! brainlift-recording 4
+//! source-hash 5d5d0bbbb2a5b71c
+//! array-size 30000
+//! eof-behaviour ignore
+//! cell-size 8
+//! pointer-wrap false
+//! signed-tape false
+//! fixed-tape false
+//! input-mode byte
+//! max-steps none
+//! strict-ascii-input false
+//! max-cell-value none
+//!
+//! <input bytes until the end of the file>
+//! ```
+//!
+//! The number after `brainlift-recording` is the format version. It is increased whenever the
+//! format changes incompatibly, and recordings of other versions are rejected.
+
+use std::{
+    fs::{self, File},
+    io::{self, Read, Write},
+    path::Path,
+};
+
+use clap::ValueEnum;
+
+use crate::cli::{CellSize, EofBehaviour, InputMode};
+
+const MAGIC: &str = "brainlift-recording";
+const FORMAT_VERSION: u32 = 4;
+
+/// Everything needed to reproduce a run of a program.
+#[derive(Debug)]
+pub struct Recording {
+    pub source_hash: u64,
+    pub settings: Settings,
+    pub input: Vec<u8>,
+}
+
+/// The interpreter settings a run depends on, which a replay takes from the recording.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Settings {
+    pub array_size: usize,
+    pub eof_behaviour: EofBehaviour,
+    pub cell_size: CellSize,
+    pub pointer_wrap: bool,
+    pub signed_tape: bool,
+    pub fixed_tape: bool,
+    pub input_mode: InputMode,
+    pub max_steps: Option<u64>,
+    pub strict_ascii_input: bool,
+    /// The `--max-cell-value` given, before it's limited to the cell size.
+    pub max_cell_value: Option<u32>,
+}
+
+impl Recording {
+    /// Reads the recording at `path`, failing if it was written in another format version.
+    ///
+    /// ```
+    /// use brainlift::recording::{Recording, RecordingError};
+    ///
+    /// let path = std::env::temp_dir().join(format!("old-recording-{}", std::process::id()));
+    /// std::fs::write(&path, "brainlift-recording 3\nsource-hash 0\n\n").unwrap();
+    /// let result = Recording::load(&path);
+    /// std::fs::remove_file(&path).unwrap();
+    /// assert!(matches!(result, Err(RecordingError::UnsupportedVersion(3))));
+    /// ```
+    pub fn load(path: &Path) -> Result<Self, RecordingError> {
+        let content = fs::read(path).map_err(RecordingError::Io)?;
+
+        // the header is terminated by an empty line
+        let header_end = content
+            .windows(2)
+            .position(|w| w == b"\n\n")
+            .ok_or(RecordingError::Malformed("missing end of header"))?;
+        let header = std::str::from_utf8(&content[..header_end])
+            .map_err(|_| RecordingError::Malformed("header is not UTF-8"))?;
+        let input = content[header_end + 2..].to_vec();
+
+        let mut lines = header.lines();
+        let version = lines
+            .next()
+            .and_then(|line| line.strip_prefix(MAGIC))
+            .and_then(|version| version.trim().parse::<u32>().ok())
+            .ok_or(RecordingError::Malformed("not a brainlift recording"))?;
+        if version != FORMAT_VERSION {
+            return Err(RecordingError::UnsupportedVersion(version));
+        }
+
+        let mut field = |name: &'static str| {
+            lines
+                .next()
+                .and_then(|line| line.strip_prefix(name))
+                .map(str::trim)
+                .ok_or(RecordingError::Malformed(name))
+        };
+        let source_hash = u64::from_str_radix(field("source-hash")?, 16)
+            .map_err(|_| RecordingError::Malformed("source-hash"))?;
+        let array_size = field("array-size")?
+            .parse()
+            .map_err(|_| RecordingError::Malformed("array-size"))?;
+        let eof_behaviour = EofBehaviour::from_str(field("eof-behaviour")?, false)
+            .map_err(|_| RecordingError::Malformed("eof-behaviour"))?;
+        let cell_size = CellSize::from_str(field("cell-size")?, false)
+            .map_err(|_| RecordingError::Malformed("cell-size"))?;
+        let pointer_wrap = field("pointer-wrap")?
+            .parse()
+            .map_err(|_| RecordingError::Malformed("pointer-wrap"))?;
+        let signed_tape = field("signed-tape")?
+            .parse()
+            .map_err(|_| RecordingError::Malformed("signed-tape"))?;
+        let fixed_tape = field("fixed-tape")?
+            .parse()
+            .map_err(|_| RecordingError::Malformed("fixed-tape"))?;
+        let input_mode = InputMode::from_str(field("input-mode")?, false)
+            .map_err(|_| RecordingError::Malformed("input-mode"))?;
+        let max_steps = parse_optional(field("max-steps")?)
+            .map_err(|_| RecordingError::Malformed("max-steps"))?;
+        let strict_ascii_input = field("strict-ascii-input")?
+            .parse()
+            .map_err(|_| RecordingError::Malformed("strict-ascii-input"))?;
+        let max_cell_value = parse_optional(field("max-cell-value")?)
+            .map_err(|_| RecordingError::Malformed("max-cell-value"))?;
+
+        Ok(Self {
+            source_hash,
+            settings: Settings {
+                array_size,
+                eof_behaviour,
+                cell_size,
+                pointer_wrap,
+                signed_tape,
+                fixed_tape,
+                input_mode,
+                max_steps,
+                strict_ascii_input,
+                max_cell_value,
+            },
+            input,
+        })
+    }
+
+    /// Whether the recording was made with the program `source`, comments included.
+    ///
+    /// ```
+    /// use brainlift::recording::Recording;
+    /// # use brainlift::{EofBehaviour, cli::{CellSize, InputMode}, recording::{self, Settings}};
+    /// # let settings = Settings {
+    /// #     array_size: 1, eof_behaviour: EofBehaviour::Ignore, cell_size: CellSize::Bits8,
+    /// #     pointer_wrap: false, signed_tape: false, fixed_tape: false,
+    /// #     input_mode: InputMode::Byte, max_steps: None, strict_ascii_input: false,
+    /// #     max_cell_value: None,
+    /// # };
+    ///
+    /// let recording = Recording {
+    ///     source_hash: recording::source_hash(",[.,]"),
+    ///     settings,
+    ///     input: Vec::new(),
+    /// };
+    /// assert!(recording.matches_source(",[.,]"));
+    /// assert!(!recording.matches_source(",[.,] reads a line"));
+    /// ```
+    pub fn matches_source(&self, source: &str) -> bool {
+        source_hash(source) == self.source_hash
+    }
+}
+
+/// Parses an optional limit, which is written as `none` if there is none.
+fn parse_optional<T: std::str::FromStr>(value: &str) -> Result<Option<T>, T::Err> {
+    match value {
+        "none" => Ok(None),
+        value => value.parse().map(Some),
+    }
+}
+
+fn format_optional(value: Option<impl std::fmt::Display>) -> String {
+    value.map_or_else(|| "none".to_owned(), |value| value.to_string())
+}
+
+/// A stable hash of a program's source, used to check that a recording is replayed with the
+/// program it was made with (FNV-1a, as it must not change between builds).
+pub fn source_hash(source: &str) -> u64 {
+    source.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Passes through everything read from `inner`, appending it to a recording. The header is
+/// written when it is created.
+///
+/// Replaying a recording gives the same output as the recorded run:
+///
+/// ```
+/// use brainlift::{
+///     EofBehaviour, Interpreter, Program,
+///     cli::{CellSize, InputMode},
+///     recording::{Recorder, Recording, Settings},
+/// };
+///
+/// let source = ",[.,]";
+/// let program = Program::parse(source).unwrap();
+/// let settings = Settings {
+///     array_size: 100,
+///     eof_behaviour: EofBehaviour::Zero,
+///     cell_size: CellSize::Bits8,
+///     pointer_wrap: false,
+///     signed_tape: false,
+///     fixed_tape: false,
+///     input_mode: InputMode::Byte,
+///     max_steps: None,
+///     strict_ascii_input: false,
+///     max_cell_value: None,
+/// };
+/// let path = std::env::temp_dir().join(format!("recording-{}", std::process::id()));
+///
+/// let mut recorded = Vec::new();
+/// let recorder = Recorder::create(&path, &b"hello"[..], source, &settings).unwrap();
+/// Interpreter::new(settings.array_size, settings.eof_behaviour)
+///     .with_io(recorder, &mut recorded)
+///     .run(&program)
+///     .unwrap();
+///
+/// let recording = Recording::load(&path).unwrap();
+/// std::fs::remove_file(&path).unwrap();
+/// assert_eq!(recording.settings, settings);
+/// assert!(recording.matches_source(source));
+///
+/// let mut replayed = Vec::new();
+/// Interpreter::new(settings.array_size, settings.eof_behaviour)
+///     .with_io(&recording.input[..], &mut replayed)
+///     .run(&program)
+///     .unwrap();
+/// assert_eq!(recorded, b"hello");
+/// assert_eq!(replayed, recorded);
+/// ```
+pub struct Recorder<R: Read> {
+    inner: R,
+    file: File,
+}
+
+impl<R: Read> Recorder<R> {
+    pub fn create(path: &Path, inner: R, source: &str, settings: &Settings) -> io::Result<Self> {
+        let mut file = File::create(path)?;
+        let name = |value: Option<clap::builder::PossibleValue>| {
+            value.expect("no variant is skipped").get_name().to_owned()
+        };
+
+        writeln!(file, "{MAGIC} {FORMAT_VERSION}")?;
+        writeln!(file, "source-hash {:016x}", source_hash(source))?;
+        writeln!(file, "array-size {}", settings.array_size)?;
+        writeln!(
+            file,
+            "eof-behaviour {}",
+            name(settings.eof_behaviour.to_possible_value())
+        )?;
+        writeln!(
+            file,
+            "cell-size {}",
+            name(settings.cell_size.to_possible_value())
+        )?;
+        writeln!(file, "pointer-wrap {}", settings.pointer_wrap)?;
+        writeln!(file, "signed-tape {}", settings.signed_tape)?;
+        writeln!(file, "fixed-tape {}", settings.fixed_tape)?;
+        writeln!(
+            file,
+            "input-mode {}",
+            name(settings.input_mode.to_possible_value())
+        )?;
+        writeln!(file, "max-steps {}", format_optional(settings.max_steps))?;
+        writeln!(file, "strict-ascii-input {}", settings.strict_ascii_input)?;
+        writeln!(
+            file,
+            "max-cell-value {}",
+            format_optional(settings.max_cell_value)
+        )?;
+        writeln!(file)?;
+
+        Ok(Self { inner, file })
+    }
+}
+
+impl<R: Read> Read for Recorder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.file.write_all(&buf[..n])?;
+        Ok(n)
+    }
+}
+
+#[derive(Debug)]
+pub enum RecordingError {
+    Io(io::Error),
+    Malformed(&'static str),
+    UnsupportedVersion(u32),
+}
+
+impl std::fmt::Display for RecordingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RecordingError::Io(e) => write!(f, "{e}"),
+            RecordingError::Malformed(what) => write!(f, "malformed recording: {what}"),
+            RecordingError::UnsupportedVersion(version) => {
+                write!(
+                    f,
+                    "unsupported recording version {version}, expected {FORMAT_VERSION}"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for RecordingError {}
@@ -0,0 +1,159 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    cli::StatsFormat,
+    parser::Parser,
+    program::{Instruction, Program},
+};
+
+/// File extensions recognized as brainfuck sources when collecting a directory.
+const SOURCE_EXTENSIONS: [&str; 2] = ["b", "bf"];
+
+#[derive(Debug, Default)]
+pub struct ProgramStats {
+    /// Number of instructions, counting each loop as a single instruction besides its body.
+    pub instructions: usize,
+    pub loops: usize,
+    pub max_nesting: usize,
+    /// Loop depth averaged over all instructions.
+    pub average_nesting: f64,
+}
+
+impl ProgramStats {
+    pub fn of(program: &Program) -> Self {
+        let mut stats = Self::default();
+        let mut total_depth = 0;
+        stats.visit(&program.instructions, 0, &mut total_depth);
+        if stats.instructions > 0 {
+            stats.average_nesting = total_depth as f64 / stats.instructions as f64;
+        }
+        stats
+    }
+
+    fn visit(&mut self, instructions: &[Instruction], depth: usize, total_depth: &mut usize) {
+        for instruction in instructions {
+            self.instructions += 1;
+            *total_depth += depth;
+
+            if let Instruction::Loop(body) = instruction {
+                self.loops += 1;
+                self.max_nesting = self.max_nesting.max(depth + 1);
+                self.visit(body, depth + 1, total_depth);
+            }
+        }
+    }
+}
+
+/// Parses `path`, or every source file in it if it is a directory (descending into
+/// subdirectories if `recursive` is set), and returns the statistics or the error for each file.
+pub fn collect(
+    path: &Path,
+    recursive: bool,
+) -> io::Result<Vec<(PathBuf, Result<ProgramStats, String>)>> {
+    let mut files = Vec::new();
+    if path.is_dir() {
+        find_sources(path, recursive, &mut files)?;
+        files.sort();
+    } else {
+        files.push(path.to_path_buf());
+    }
+
+    Ok(files
+        .into_iter()
+        .map(|file| {
+            let stats = fs::read_to_string(&file)
+                .map_err(|e| e.to_string())
+                .and_then(|content| Parser::new(&content).parse().map_err(|e| e.to_string()))
+                .map(|program| ProgramStats::of(&program));
+            (file, stats)
+        })
+        .collect())
+}
+
+fn find_sources(dir: &Path, recursive: bool, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            if recursive {
+                find_sources(&path, recursive, files)?;
+            }
+        } else if path
+            .extension()
+            .is_some_and(|ext| SOURCE_EXTENSIONS.iter().any(|known| ext == *known))
+        {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+pub fn print(results: &[(PathBuf, Result<ProgramStats, String>)], format: StatsFormat) {
+    match format {
+        StatsFormat::Table => print_table(results),
+        StatsFormat::Csv => print_csv(results),
+    }
+}
+
+fn print_table(results: &[(PathBuf, Result<ProgramStats, String>)]) {
+    println!(
+        "{:<32} {:>12} {:>8} {:>11} {:>11}",
+        "file", "instructions", "loops", "max nesting", "avg nesting"
+    );
+
+    let mut parsed = Vec::new();
+    for (file, stats) in results {
+        let file = file.display();
+        match stats {
+            Ok(stats) => {
+                println!(
+                    "{file:<32} {:>12} {:>8} {:>11} {:>11.2}",
+                    stats.instructions, stats.loops, stats.max_nesting, stats.average_nesting
+                );
+                parsed.push(stats);
+            }
+            Err(e) => println!("{file:<32} error: {e}"),
+        }
+    }
+
+    let instructions: usize = parsed.iter().map(|stats| stats.instructions).sum();
+    let average_nesting = if parsed.is_empty() {
+        0.0
+    } else {
+        parsed
+            .iter()
+            .map(|stats| stats.average_nesting)
+            .sum::<f64>()
+            / parsed.len() as f64
+    };
+
+    println!();
+    println!("programs:            {}", results.len());
+    println!("parse failures:      {}", results.len() - parsed.len());
+    println!("total instructions:  {instructions}");
+    println!("average nesting:     {average_nesting:.2}");
+}
+
+fn print_csv(results: &[(PathBuf, Result<ProgramStats, String>)]) {
+    println!("file,instructions,loops,max_nesting,average_nesting,error");
+    for (file, stats) in results {
+        let file = csv_field(&file.display().to_string());
+        match stats {
+            Ok(stats) => println!(
+                "{file},{},{},{},{:.2},",
+                stats.instructions, stats.loops, stats.max_nesting, stats.average_nesting
+            ),
+            Err(e) => println!("{file},,,,,{}", csv_field(e)),
+        }
+    }
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
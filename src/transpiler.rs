@@ -0,0 +1,329 @@
+use std::io::{self, Write};
+
+use crate::{
+    cli::{CellSize, EofBehaviour, WideOutput},
+    program::{Instruction, Program, Span},
+};
+
+const INDENT: &str = "    ";
+
+/// Writes a character as UTF-8 for [`WideOutput::Unicode`], aborting if it isn't one.
+const PUT_UTF8: &str = r#"static void put_utf8(uint32_t c) {
+    if (c > 0x10ffff || (c & ~0x7ffu) == 0xd800) abort();
+    if (c < 0x80) {
+        putchar(c);
+    } else if (c < 0x800) {
+        putchar(0xc0 | c >> 6);
+        putchar(0x80 | (c & 0x3f));
+    } else if (c < 0x10000) {
+        putchar(0xe0 | c >> 12);
+        putchar(0x80 | (c >> 6 & 0x3f));
+        putchar(0x80 | (c & 0x3f));
+    } else {
+        putchar(0xf0 | c >> 18);
+        putchar(0x80 | (c >> 12 & 0x3f));
+        putchar(0x80 | (c >> 6 & 0x3f));
+        putchar(0x80 | (c & 0x3f));
+    }
+}
+"#;
+
+/// Translates programs into equivalent, readable C, using `getchar` and `putchar` for I/O like
+/// the compiler does.
+pub struct Transpiler<W: Write> {
+    out: W,
+    max_array_size: usize,
+    eof_behaviour: EofBehaviour,
+    cell_size: CellSize,
+    wide_output: WideOutput,
+    pointer_wrap: bool,
+    signed_tape: bool,
+    annotate: bool,
+    depth: usize,
+    /// Index of the next instruction in source order, to look up its span.
+    next: usize,
+}
+
+impl<W: Write> Transpiler<W> {
+    pub fn new(out: W, max_array_size: usize, eof_behaviour: EofBehaviour) -> Self {
+        Self {
+            out,
+            max_array_size,
+            eof_behaviour,
+            cell_size: CellSize::Bits8,
+            wide_output: WideOutput::LowByte,
+            pointer_wrap: false,
+            signed_tape: false,
+            annotate: false,
+            depth: 1,
+            next: 0,
+        }
+    }
+
+    /// Use cells of the given width, which wrap around at that width.
+    pub fn with_cell_size(mut self, cell_size: CellSize) -> Self {
+        self.cell_size = cell_size;
+        self
+    }
+
+    /// Choose what `.` writes for cells wider than 8 bits, like the compiler. Values that can't be
+    /// written in the chosen mode abort the program.
+    pub fn with_wide_output(mut self, wide_output: WideOutput) -> Self {
+        self.wide_output = wide_output;
+        self
+    }
+
+    /// Wrap the pointer around the ends of the tape.
+    pub fn with_pointer_wrap(mut self, pointer_wrap: bool) -> Self {
+        self.pointer_wrap = pointer_wrap;
+        self
+    }
+
+    /// Start the pointer in the middle of the tape, so that it can move left of the starting cell.
+    pub fn with_signed_tape(mut self, signed_tape: bool) -> Self {
+        self.signed_tape = signed_tape;
+        self
+    }
+
+    /// Write a comment before the code of each instruction with its position and source. The
+    /// instructions introduced by optimization passes are written as the source they replace,
+    /// followed by their name.
+    ///
+    /// ```
+    /// use brainlift::{EofBehaviour, Parser, optimizer, transpiler::Transpiler};
+    ///
+    /// let mut program = Parser::new("+\n[-]>").with_spans(true).parse().unwrap();
+    /// optimizer::fold_clear_loops(&mut program);
+    ///
+    /// let mut c = Vec::new();
+    /// Transpiler::new(&mut c, 10, EofBehaviour::Ignore)
+    ///     .with_annotate(true)
+    ///     .transpile(&program)
+    ///     .unwrap();
+    /// let c = String::from_utf8(c).unwrap();
+    /// assert!(c.contains("    /* 1:1 + */\n    ++*p;\n    /* 2:1 [-] set zero */\n    *p = 0;\n"));
+    /// assert!(c.contains("    /* 2:4 > */\n    ++p;\n"));
+    ///
+    /// // off by default
+    /// let mut c = Vec::new();
+    /// Transpiler::new(&mut c, 10, EofBehaviour::Ignore).transpile(&program).unwrap();
+    /// assert!(!String::from_utf8(c).unwrap().contains("/*"));
+    /// ```
+    pub fn with_annotate(mut self, annotate: bool) -> Self {
+        self.annotate = annotate;
+        self
+    }
+
+    pub fn transpile(mut self, program: &Program) -> io::Result<()> {
+        writeln!(self.out, "#include <stdint.h>")?;
+        writeln!(self.out, "#include <stdio.h>")?;
+        writeln!(self.out, "#include <stdlib.h>")?;
+        writeln!(self.out, "#include <string.h>")?;
+        writeln!(self.out)?;
+        if self.wide_output() == WideOutput::Unicode {
+            writeln!(self.out, "{PUT_UTF8}")?;
+        }
+        writeln!(self.out, "int main(void) {{")?;
+        let cell = self.cell_type();
+        self.line(&format!(
+            "{cell} *tape = calloc({}, sizeof *tape);",
+            self.max_array_size
+        ))?;
+        if self.signed_tape {
+            self.line(&format!("{cell} *p = tape + {};", self.max_array_size / 2))?;
+        } else {
+            self.line(&format!("{cell} *p = tape;"))?;
+        }
+        writeln!(self.out)?;
+
+        let spans = program.spans.as_deref();
+        for instruction in &program.instructions {
+            self.instruction(instruction, spans)?;
+        }
+
+        writeln!(self.out)?;
+        self.line("free(tape);")?;
+        self.line("return 0;")?;
+        writeln!(self.out, "}}")?;
+        self.out.flush()
+    }
+
+    fn instruction(&mut self, instruction: &Instruction, spans: Option<&[Span]>) -> io::Result<()> {
+        let span = spans.and_then(|spans| spans.get(self.next).copied());
+        self.next += 1;
+        // `#` translates to no code
+        if self.annotate && !matches!(instruction, Instruction::Debug) {
+            self.annotation(instruction, span)?;
+        }
+
+        if let Instruction::Loop(body) = instruction {
+            self.line("while (*p) {")?;
+            self.depth += 1;
+            for instruction in body {
+                self.instruction(instruction, spans)?;
+            }
+            self.depth -= 1;
+            return self.line("}");
+        }
+        self.statement(instruction)
+    }
+
+    /// Writes the comment `--annotate` puts before the code of `instruction`.
+    fn annotation(&mut self, instruction: &Instruction, span: Option<Span>) -> io::Result<()> {
+        let mut comment = match span {
+            Some(span) => format!("{}:{} ", span.line, span.column),
+            None => String::new(),
+        };
+        match instruction {
+            // the body follows with its own comments
+            Instruction::Loop(_) => comment.push('['),
+            Instruction::OutputBytes(_) => comment.push_str(&instruction.to_string()),
+            _ if instruction.to_char().is_none() => {
+                comment.push_str(&format!("{instruction} {}", instruction.name()));
+            }
+            _ => comment.push_str(&instruction.to_string()),
+        }
+        self.line(&format!("/* {comment} */"))
+    }
+
+    /// Writes the code of an instruction other than a loop.
+    fn statement(&mut self, instruction: &Instruction) -> io::Result<()> {
+        match instruction {
+            Instruction::Increment => self.line("++*p;"),
+            Instruction::Decrement => self.line("--*p;"),
+            Instruction::Add(n) if *n < 0 => self.line(&format!("*p -= {};", n.unsigned_abs())),
+            Instruction::Add(n) => self.line(&format!("*p += {n};")),
+            Instruction::Right | Instruction::Left | Instruction::Move(_) if self.pointer_wrap => {
+                let offset = match instruction {
+                    Instruction::Right => 1,
+                    Instruction::Left => -1,
+                    Instruction::Move(offset) => *offset as i128,
+                    _ => unreachable!(),
+                };
+                let size = self.max_array_size as i128;
+                self.line(&format!(
+                    "p = tape + (p - tape + {}) % {size};",
+                    offset.rem_euclid(size)
+                ))
+            }
+            Instruction::Right => self.line("++p;"),
+            Instruction::Left => self.line("--p;"),
+            Instruction::Move(offset) if *offset < 0 => {
+                self.line(&format!("p -= {};", offset.unsigned_abs()))
+            }
+            Instruction::Move(offset) => self.line(&format!("p += {offset};")),
+            Instruction::SetZero => self.line("*p = 0;"),
+            Instruction::Set(value) => self.line(&format!("*p = {value};")),
+            Instruction::MultiplyAdd { targets } => {
+                self.line("if (*p) {")?;
+                self.depth += 1;
+                for &(offset, factor) in targets {
+                    let cell = self.cell(offset);
+                    if factor < 0 {
+                        self.line(&format!("{cell} -= *p * {};", factor.unsigned_abs()))?;
+                    } else {
+                        self.line(&format!("{cell} += *p * {factor};"))?;
+                    }
+                }
+                self.line("*p = 0;")?;
+                self.depth -= 1;
+                self.line("}")
+            }
+            Instruction::Scan { step } => {
+                self.line("while (*p) {")?;
+                self.depth += 1;
+                self.statement(&Instruction::Move(*step))?;
+                self.depth -= 1;
+                self.line("}")
+            }
+            Instruction::Output => match self.wide_output() {
+                WideOutput::LowByte => self.line("putchar(*p);"),
+                WideOutput::Error => {
+                    self.line("if (*p > 255) abort();")?;
+                    self.line("putchar(*p);")
+                }
+                WideOutput::Unicode => self.line("put_utf8(*p);"),
+            },
+            Instruction::OutputBytes(bytes) => self.line(&format!(
+                "fwrite({}, 1, {}, stdout);",
+                c_string(bytes),
+                bytes.len()
+            )),
+            Instruction::InitCells {
+                base_offset,
+                values,
+            } => {
+                let values: Vec<_> = values.iter().map(u8::to_string).collect();
+                self.line(&format!(
+                    "memcpy(p + {base_offset}, ({}[]){{{}}}, {} * sizeof *p);",
+                    self.cell_type(),
+                    values.join(", "),
+                    values.len()
+                ))
+            }
+            Instruction::Input => match self.eof_behaviour {
+                EofBehaviour::Ignore => self.line("{ int c = getchar(); if (c != EOF) *p = c; }"),
+                EofBehaviour::Zero => self.line("{ int c = getchar(); *p = c == EOF ? 0 : c; }"),
+                EofBehaviour::Negative => {
+                    self.line("{ int c = getchar(); *p = c == EOF ? -1 : c; }")
+                }
+            },
+            // like the compiler, there is no state dump in compiled programs
+            Instruction::Debug => Ok(()),
+            Instruction::Loop(_) => unreachable!("loops are written by `instruction`"),
+        }
+    }
+
+    /// Returns the C expression for the cell `offset` cells from the pointer.
+    fn cell(&self, offset: isize) -> String {
+        if self.pointer_wrap {
+            let size = self.max_array_size as i128;
+            format!(
+                "tape[(p - tape + {}) % {size}]",
+                (offset as i128).rem_euclid(size)
+            )
+        } else {
+            format!("p[{offset}]")
+        }
+    }
+
+    /// The mode for `.`, in which 8-bit cells are always written as they are.
+    fn wide_output(&self) -> WideOutput {
+        match self.cell_size {
+            CellSize::Bits8 => WideOutput::LowByte,
+            _ => self.wide_output,
+        }
+    }
+
+    fn cell_type(&self) -> &'static str {
+        match self.cell_size {
+            CellSize::Bits8 => "uint8_t",
+            CellSize::Bits16 => "uint16_t",
+            CellSize::Bits32 => "uint32_t",
+        }
+    }
+
+    fn line(&mut self, line: &str) -> io::Result<()> {
+        writeln!(self.out, "{}{line}", INDENT.repeat(self.depth))
+    }
+}
+
+/// Formats `bytes` as a C string literal. Everything but printable ASCII is written as an octal
+/// escape, which unlike a hex escape can't swallow the following character.
+fn c_string(bytes: &[u8]) -> String {
+    let mut literal = String::from("\"");
+    for &byte in bytes {
+        match byte {
+            b'"' | b'\\' => {
+                literal.push('\\');
+                literal.push(byte as char);
+            }
+            // `?` could start a trigraph
+            b'?' => literal.push_str("\\?"),
+            b' '..=b'~' => literal.push(byte as char),
+            _ => literal.push_str(&format!("\\{byte:03o}")),
+        }
+    }
+    literal.push('"');
+    literal
+}